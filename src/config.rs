@@ -0,0 +1,346 @@
+use std::fs;
+use std::path::Path;
+
+use toml::Value;
+
+use filter::{self, FilterMode};
+use palette::{self, Palette};
+
+/// The file read from the working directory when `--config` is not given.
+pub const DEFAULT_PATH: &str = "chip8.toml";
+
+/// Window size in host pixels per CHIP-8 pixel when nothing else is configured.
+pub const DEFAULT_SCALE: u32 = 20;
+
+/// How many game controllers can be mapped, one per player.
+pub const CONTROLLER_COUNT: usize = 2;
+
+/// Host key names for each keypad key on a QWERTY keyboard, in keypad order 0-F.
+const QWERTY: [&[&str]; 16] = [
+    &["X"], &["1"], &["2"], &["3"],
+    &["Q"], &["W"], &["E"], &["A"],
+    &["S"], &["D"], &["Z"], &["C"],
+    &["4"], &["R"], &["F"], &["V"],
+];
+
+/// Controller buttons for each keypad key: the D-pad on 2/4/6/8 and the face buttons on 5.
+const GAMEPAD: [&[&str]; 16] = [
+    &[], &[], &["dpup"], &[],
+    &["dpleft"], &["a", "b"], &["dpright"], &[],
+    &["dpdown"], &[], &[], &[],
+    &[], &[], &[], &[],
+];
+
+/// Host key or button names for each of the 16 keypad keys.
+pub type KeyMap = [Vec<String>; 16];
+
+/// Settings read from a TOML file such as:
+///
+/// ```toml
+/// [display]
+/// palette = "amber"
+/// colours = ["#000000"]
+/// scale = 10
+/// integer_scale = true
+/// fullscreen = false
+/// vsync = false
+/// filter = "phosphor"
+/// decay = 0.6
+///
+/// [keyboard]
+/// 5 = ["W", "Up"]
+///
+/// [roms."pong.ch8".controller1]
+/// 1 = "dpup"
+/// 4 = "dpdown"
+///
+/// [roms."pong.ch8".controller2]
+/// C = "dpup"
+/// D = "dpdown"
+/// ```
+///
+/// A `roms` table named after the ROM's file name is applied on top of the global settings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// SDL key names held down for each keypad key.
+    pub keyboard: KeyMap,
+    /// SDL game controller button names for each player's controller.
+    pub controllers: [KeyMap; CONTROLLER_COUNT],
+    /// A named palette with any custom `colours` laid over it.
+    pub palette: Palette,
+    /// Initial window size in host pixels per CHIP-8 pixel.
+    pub scale: u32,
+    /// Keeps CHIP-8 pixels a whole number of host pixels when the window is resized.
+    pub integer_scale: bool,
+    pub fullscreen: bool,
+    /// Paces frames by the display's refresh instead of a 60 Hz timer, which only keeps the
+    /// right speed on a 60 Hz display.
+    pub vsync: bool,
+    pub filter: FilterMode,
+    /// Share of a pixel's glow kept each frame by the phosphor filter.
+    pub decay: f32,
+    /// Frames combined by the `or` filter.
+    pub blend_frames: usize,
+}
+
+impl Config {
+    pub fn load(path: &str, rom_path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Config::parse(&text, rom_path).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn parse(text: &str, rom_path: &str) -> Result<Self, String> {
+        let root: Value = text.parse().map_err(|err: toml::de::Error| err.to_string())?;
+        let mut config = Config::default();
+        config.apply(&root)?;
+
+        let rom_name = Path::new(rom_path).file_name().and_then(|name| name.to_str());
+        let rom = rom_name.and_then(|name| root.get("roms").and_then(|roms| roms.get(name)));
+        if let Some(rom) = rom {
+            config.apply(rom)
+                .map_err(|err| format!("roms.\"{}\": {}", rom_name.unwrap_or(""), err))?;
+        }
+
+        Ok(config)
+    }
+
+    fn apply(&mut self, table: &Value) -> Result<(), String> {
+        apply_map(&mut self.keyboard, table, "keyboard")?;
+        for (player, controller) in self.controllers.iter_mut().enumerate() {
+            apply_map(controller, table, &format!("controller{}", player + 1))?;
+        }
+        if let Some(display) = table.get("display") {
+            self.apply_display(display).map_err(|err| format!("display: {}", err))?;
+        }
+        Ok(())
+    }
+
+    fn apply_display(&mut self, display: &Value) -> Result<(), String> {
+        if let Some(name) = display.get("palette") {
+            let name = name.as_str().ok_or("palette must be a name")?;
+            self.palette = Palette::parse(name)?;
+        }
+
+        if let Some(colours) = display.get("colours") {
+            let colours = strings(colours).ok_or("colours must be a list of #rrggbb strings")?;
+            let colours = palette::parse_colours(&colours.join(","))?;
+            self.palette = self.palette.with_colours(&colours);
+        }
+
+        if let Some(scale) = display.get("scale") {
+            self.scale = match scale.as_integer() {
+                Some(scale) if (1..=100).contains(&scale) => scale as u32,
+                _ => return Err("scale must be a whole number from 1 to 100".to_string()),
+            };
+        }
+        if let Some(integer_scale) = display.get("integer_scale") {
+            self.integer_scale = integer_scale.as_bool().ok_or("integer_scale must be true or false")?;
+        }
+        if let Some(fullscreen) = display.get("fullscreen") {
+            self.fullscreen = fullscreen.as_bool().ok_or("fullscreen must be true or false")?;
+        }
+        if let Some(vsync) = display.get("vsync") {
+            self.vsync = vsync.as_bool().ok_or("vsync must be true or false")?;
+        }
+
+        if let Some(name) = display.get("filter") {
+            let name = name.as_str().ok_or("filter must be a name")?;
+            self.filter = FilterMode::from_name(name)
+                .ok_or_else(|| format!("unknown filter '{}', expected off, phosphor or or", name))?;
+        }
+        if let Some(decay) = display.get("decay") {
+            self.decay = match decay.as_float() {
+                Some(decay) if (0.0..1.0).contains(&decay) => decay as f32,
+                _ => return Err("decay must be a number from 0.0 up to 1.0".to_string()),
+            };
+        }
+        if let Some(frames) = display.get("blend_frames") {
+            self.blend_frames = match frames.as_integer() {
+                Some(frames) if (1..=60).contains(&frames) => frames as usize,
+                _ => return Err("blend_frames must be a whole number from 1 to 60".to_string()),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keyboard: key_map(&QWERTY),
+            controllers: [key_map(&GAMEPAD), key_map(&GAMEPAD)],
+            palette: Palette::default(),
+            scale: DEFAULT_SCALE,
+            integer_scale: false,
+            fullscreen: false,
+            vsync: false,
+            filter: FilterMode::Off,
+            decay: filter::DEFAULT_DECAY,
+            blend_frames: filter::DEFAULT_BLEND_FRAMES,
+        }
+    }
+}
+
+fn key_map(defaults: &[&[&str]; 16]) -> KeyMap {
+    let mut map: KeyMap = Default::default();
+    for (names, defaults) in map.iter_mut().zip(defaults.iter()) {
+        *names = defaults.iter().map(|name| name.to_string()).collect();
+    }
+    map
+}
+
+/// Replaces the keypad keys listed in the named table, if there is one.
+fn apply_map(map: &mut KeyMap, table: &Value, table_name: &str) -> Result<(), String> {
+    let entries = match table.get(table_name) {
+        Some(Value::Table(entries)) => entries,
+        Some(_) => return Err(format!("{} must be a table", table_name)),
+        None => return Ok(()),
+    };
+
+    for (name, value) in entries {
+        let key = match usize::from_str_radix(name, 16) {
+            Ok(key) if key < 16 && name.len() == 1 => key,
+            _ => return Err(format!("{}: '{}' is not a keypad key", table_name, name)),
+        };
+        let host_keys = strings(value).ok_or_else(|| {
+            format!("{}.{}: expected a key name or a list of them", table_name, name)
+        })?;
+
+        // A host key moved to a new keypad key stops pressing the one it used to.
+        for (other, names) in map.iter_mut().enumerate() {
+            if other != key {
+                names.retain(|old| !host_keys.iter().any(|new| new.eq_ignore_ascii_case(old)));
+            }
+        }
+        map[key] = host_keys;
+    }
+
+    Ok(())
+}
+
+/// A single string or a list of strings.
+fn strings(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(name) => Some(vec![name.clone()]),
+        Value::Array(names) => names.iter().map(|name| name.as_str().map(String::from)).collect(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(config: &Config, key: usize) -> Vec<&str> {
+        config.keyboard[key].iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn empty_file_keeps_qwerty_layout() {
+        let config = Config::parse("", "pong.ch8").unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(keys(&config, 0xc), ["4"]);
+        assert_eq!(keys(&config, 0x0), ["X"]);
+    }
+
+    #[test]
+    fn several_host_keys_map_to_one_keypad_key() {
+        let config = Config::parse("[keyboard]\n5 = [\"W\", \"Up\"]\nA = \"Space\"\n", "game.ch8").unwrap();
+        assert_eq!(keys(&config, 0x5), ["W", "Up"]);
+        assert_eq!(keys(&config, 0xa), ["Space"]);
+        assert_eq!(keys(&config, 0x4), ["Q"]);
+    }
+
+    #[test]
+    fn reassigned_host_key_leaves_its_old_keypad_key() {
+        let config = Config::parse("[keyboard]\n2 = \"w\"\n", "game.ch8").unwrap();
+        assert_eq!(keys(&config, 0x2), ["w"]);
+        assert!(keys(&config, 0x5).is_empty());
+    }
+
+    #[test]
+    fn rom_overrides_apply_only_to_that_rom() {
+        let text = "[keyboard]\n1 = \"Z\"\n[roms.\"pong.ch8\".keyboard]\n1 = \"Up\"\n4 = \"Down\"\n";
+
+        let pong = Config::parse(text, "roms/pong.ch8").unwrap();
+        assert_eq!(keys(&pong, 0x1), ["Up"]);
+        assert_eq!(keys(&pong, 0x4), ["Down"]);
+
+        let other = Config::parse(text, "roms/tetris.ch8").unwrap();
+        assert_eq!(keys(&other, 0x1), ["Z"]);
+        assert!(keys(&other, 0xa).is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_keypad_keys() {
+        assert!(Config::parse("[keyboard]\n10 = \"A\"\n", "game.ch8").is_err());
+        assert!(Config::parse("[keyboard]\nG = \"A\"\n", "game.ch8").is_err());
+        assert!(Config::parse("[keyboard]\n1 = 5\n", "game.ch8").is_err());
+        assert!(Config::parse("[controller1]\nG = \"a\"\n", "game.ch8").is_err());
+    }
+
+    #[test]
+    fn controllers_default_to_dpad_and_face_buttons() {
+        let config = Config::default();
+        for controller in &config.controllers {
+            assert_eq!(controller[0x2], ["dpup"]);
+            assert_eq!(controller[0x4], ["dpleft"]);
+            assert_eq!(controller[0x6], ["dpright"]);
+            assert_eq!(controller[0x8], ["dpdown"]);
+            assert_eq!(controller[0x5], ["a", "b"]);
+        }
+    }
+
+    #[test]
+    fn players_get_separate_controller_maps_per_rom() {
+        let text = "[roms.\"pong.ch8\".controller1]\n1 = \"dpup\"\n4 = \"dpdown\"\n\
+                    [roms.\"pong.ch8\".controller2]\nC = \"dpup\"\nD = \"dpdown\"\n";
+        let config = Config::parse(text, "pong.ch8").unwrap();
+
+        let [ref one, ref two] = config.controllers;
+        assert_eq!(one[0x1], ["dpup"]);
+        assert_eq!(one[0x4], ["dpdown"]);
+        assert_eq!(two[0xc], ["dpup"]);
+        assert_eq!(two[0xd], ["dpdown"]);
+        assert!(one[0x2].is_empty() && one[0x8].is_empty());
+        assert!(two[0x1].is_empty() && two[0x2].is_empty());
+        assert_eq!(config.keyboard, Config::default().keyboard);
+    }
+
+    #[test]
+    fn display_palette_with_custom_colours() {
+        let text = "[display]\npalette = \"lcd\"\n[roms.\"pong.ch8\".display]\ncolours = [\"#000000\"]\n";
+
+        assert_eq!(Config::parse(text, "tetris.ch8").unwrap().palette, Palette::lcd());
+        assert_eq!(Config::parse(text, "pong.ch8").unwrap().palette, Palette::lcd().with_colours(&[(0, 0, 0)]));
+        assert!(Config::parse("[display]\npalette = \"sepia\"\n", "pong.ch8").is_err());
+    }
+
+    #[test]
+    fn display_size_settings() {
+        let text = "[display]\nscale = 8\ninteger_scale = true\nfullscreen = true\nvsync = true\n";
+        let config = Config::parse(text, "pong.ch8").unwrap();
+        assert_eq!((config.scale, config.integer_scale, config.fullscreen), (8, true, true));
+        assert!(config.vsync);
+
+        assert_eq!(Config::default().scale, DEFAULT_SCALE);
+        assert!(Config::parse("[display]\nscale = 0\n", "pong.ch8").is_err());
+        assert!(Config::parse("[display]\nfullscreen = 1\n", "pong.ch8").is_err());
+    }
+
+    #[test]
+    fn display_filter_settings() {
+        let text = "[display]\nfilter = \"phosphor\"\ndecay = 0.75\n\
+                    [roms.\"pong.ch8\".display]\nfilter = \"or\"\nblend_frames = 3\n";
+
+        let tetris = Config::parse(text, "tetris.ch8").unwrap();
+        assert_eq!((tetris.filter, tetris.decay), (FilterMode::Phosphor, 0.75));
+
+        let pong = Config::parse(text, "pong.ch8").unwrap();
+        assert_eq!((pong.filter, pong.blend_frames), (FilterMode::Or, 3));
+
+        assert!(Config::parse("[display]\ndecay = 1.5\n", "pong.ch8").is_err());
+        assert!(Config::parse("[display]\nfilter = \"blur\"\n", "pong.ch8").is_err());
+    }
+}
@@ -1,16 +1,98 @@
-use rand;
-use rand::Rng;
-
 use CHIP8_WIDTH;
 use CHIP8_HEIGHT;
+use SCHIP_WIDTH;
+use SCHIP_HEIGHT;
+use Framebuffer;
+use error::EmulatorError;
+use instruction::{self, Instruction, OPCODE_SIZE};
+use memory::{BIG_FONT_ADDRESS, PROGRAM_START};
+use quirks::Quirks;
+use random::{Random, RandomMode};
+use savestate::{self, StateReader, StateWriter};
+
+
+pub const DEFAULT_CYCLES_PER_FRAME: usize = 10;
+
+pub const RPL_FLAG_COUNT: usize = 16;
+
+/// Machine cycles between two COSMAC VIP vertical blank interrupts.
+pub const VIP_CYCLES_PER_FRAME: usize = 3668;
+
+const PLANE_COUNT: usize = 2;
+
+/// Stands in for a key in save states where there is none.
+const NO_KEY: u8 = 0xff;
+
+/// What to do when the program executes an opcode that has no meaning, including `0NNN`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strictness {
+    Ignore,
+    Log,
+    Halt,
+}
+
+impl Strictness {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ignore" => Some(Strictness::Ignore),
+            "log" => Some(Strictness::Log),
+            "halt" => Some(Strictness::Halt),
+            _ => None,
+        }
+    }
+}
+
+/// How much work fits into one 60 Hz frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+    /// A fixed number of instructions per frame, whatever they are.
+    Fixed,
+    /// Each instruction costs its VIP machine cycles out of `VIP_CYCLES_PER_FRAME`, and
+    /// sprite draws wait for the next frame.
+    CosmacVip,
+}
+
+impl Timing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fixed" => Some(Timing::Fixed),
+            "vip" => Some(Timing::CosmacVip),
+            _ => None,
+        }
+    }
+}
+
+/// How far an `FX0A` instruction has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyWait {
+    Idle,
+    /// Waiting for any key to go down, to be stored in Vx.
+    Press { x: usize },
+    /// `key` is down, and the instruction completes once it is released.
+    Release { x: usize, key: usize },
+}
 
+/// An XO-CHIP audio pattern: 128 one-bit samples played back at a rate set by the pitch register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub buffer: [u8; 16],
+    pub pitch: u8,
+}
 
-const OPCODE_SIZE: usize = 2;
+impl AudioPattern {
+    /// Playback rate in bits per second.
+    pub fn sample_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+}
 
 pub struct Output<'a> {
-    pub video_memory: &'a [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+    pub video_memory: &'a Framebuffer,
     pub video_memory_changed: bool,
     pub beep: bool,
+    pub audio_pattern: Option<AudioPattern>,
+    pub waiting_for_key: bool,
+    pub exited: bool,
 }
 
 enum ProgramCounter {
@@ -28,6 +110,7 @@ impl ProgramCounter {
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     registers : [u8; 16],
     register_i : usize,
@@ -36,162 +119,567 @@ pub struct CPU {
     stack_pointer: usize,
     delay_timer: u8,
     sound_timer: u8,
-    memory : [u8; 4096],
+    memory : Vec<u8>,
     video_memory_changed : bool,
-    video_memory: [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+    video_memory: Framebuffer,
     keypad: [bool; 16],
-    keypad_register: usize,
-    keypad_waiting: bool,
-    beep: bool,
-
+    key_wait: KeyWait,
+    display_waiting: bool,
+    rpl_flags: [u8; RPL_FLAG_COUNT],
+    plane_mask: u8,
+    audio_buffer: Option<[u8; 16]>,
+    pitch: u8,
+    exited: bool,
+    memory_writes: Vec<usize>,
+    quirks: Quirks,
+    strictness: Strictness,
+    cycles_per_frame: usize,
+    timing: Timing,
+    frame_cycles: usize,
+    random: Random,
 }
 
 impl CPU {
-    pub fn new(memory: [u8; 4096]) -> Self {
-        let cpu  = CPU {
+    /// Creates a CPU whose `CXKK` results are fully determined by `seed`.
+    pub fn new(memory: Vec<u8>, quirks: Quirks, seed: u64) -> Self {
+        CPU {
             registers: [0; 16],
-            register_i: 0x200,
-            program_counter: 0x200,
+            register_i: PROGRAM_START,
+            program_counter: PROGRAM_START,
             stack: [0; 16],
             stack_pointer: 0,
             delay_timer: 0,
             sound_timer: 0,
             memory,
             video_memory_changed: false,
-            video_memory: [[0; CHIP8_WIDTH]; CHIP8_HEIGHT],
+            video_memory: Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT),
             keypad: [false; 16],
-            keypad_register: 0,
-            keypad_waiting: false,
-            beep: false,
-        };
-        cpu
+            key_wait: KeyWait::Idle,
+            display_waiting: false,
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            plane_mask: 1,
+            audio_buffer: None,
+            pitch: 64,
+            exited: false,
+            memory_writes: Vec::new(),
+            quirks,
+            strictness: Strictness::Ignore,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            timing: Timing::Fixed,
+            frame_cycles: 0,
+            random: Random::new(seed),
+        }
+    }
+
+    /// Runs one 60 Hz frame: ticks the timers, then executes instructions until the frame is over.
+    pub fn cpu_cycle(&mut self, keypad: [bool; 16]) -> Result<Output<'_>, EmulatorError> {
+        self.begin_frame(keypad);
+
+        while !self.frame_finished() {
+            self.step_instruction()?;
+        }
+
+        Ok(self.output())
     }
 
-    pub fn cpu_cycle(&mut self, keypad: [bool; 16]) -> Output {
+    /// Starts a frame without running it, for callers that step the instructions themselves.
+    pub fn begin_frame(&mut self, keypad: [bool; 16]) {
         self.keypad = keypad;
         self.video_memory_changed = false;
 
-        if self.keypad_waiting {
-            for i in 0..keypad.len() {
-                if keypad[i] {
-                    self.keypad_waiting = false;
-                    self.registers[self.keypad_register] = i as u8;
-                    break;
+        // An instruction that ran past the end of the last VIP frame eats into this one.
+        self.frame_cycles = match self.timing {
+            Timing::Fixed => 0,
+            Timing::CosmacVip => self.frame_cycles.saturating_sub(VIP_CYCLES_PER_FRAME),
+        };
+
+        self.tick_timers();
+    }
+
+    /// The state of the frame so far.
+    pub fn output(&self) -> Output<'_> {
+        Output {
+            video_memory: &self.video_memory,
+            video_memory_changed: self.video_memory_changed,
+            beep: self.sound_timer > 0,
+            audio_pattern: self.audio_buffer.map(|buffer| AudioPattern { buffer, pitch: self.pitch }),
+            waiting_for_key: self.key_wait != KeyWait::Idle,
+            exited: self.exited,
+        }
+    }
+
+    pub fn step_instruction(&mut self) -> Result<(), EmulatorError> {
+        self.memory_writes.clear();
+
+        if self.exited {
+            return Ok(());
+        }
+
+        match self.key_wait {
+            KeyWait::Idle => if !self.display_waiting {
+                self.opcode_execute()?;
+            },
+            KeyWait::Press { x } => {
+                if let Some(key) = self.keypad.iter().position(|&pressed| pressed) {
+                    if self.quirks.wait_key_release {
+                        self.key_wait = KeyWait::Release { x, key };
+                    } else {
+                        self.registers[x] = key as u8;
+                        self.key_wait = KeyWait::Idle;
+                    }
                 }
             }
+            KeyWait::Release { x, key } => if !self.keypad[key] {
+                self.registers[x] = key as u8;
+                self.key_wait = KeyWait::Idle;
+            },
+        }
+
+        if self.timing == Timing::Fixed {
+            self.frame_cycles += 1;
         }
-        else {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
+        Ok(())
+    }
+
+    /// Whether the current frame has no time left. An exited program never runs again; on
+    /// the VIP, waiting for a key or for the vertical blank also idles away the rest of the frame.
+    pub fn frame_finished(&self) -> bool {
+        if self.exited {
+            return true;
+        }
+
+        match self.timing {
+            Timing::Fixed => self.frame_cycles >= self.cycles_per_frame,
+            Timing::CosmacVip => {
+                let key_blocked = match self.key_wait {
+                    KeyWait::Idle => false,
+                    KeyWait::Press { .. } => !self.keypad.contains(&true),
+                    KeyWait::Release { key, .. } => self.keypad[key],
+                };
+                let idle = self.display_waiting || key_blocked;
+                idle || self.frame_cycles >= VIP_CYCLES_PER_FRAME
             }
+        }
+    }
 
-           if self.sound_timer > 0 {
-               self.sound_timer -= 1;
-           }
-            self.opcode_execute();
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
         }
 
-        Output {
-            video_memory: &self.video_memory,
-            video_memory_changed: self.video_memory_changed,
-            beep: self.sound_timer > 0,
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
         }
 
+        self.random.tick();
+
+        // The timers are driven by the vertical blank, which is what a sprite draw waits on.
+        self.display_waiting = false;
     }
 
-    pub fn opcode_execute(&mut self) {
-
-        let opcode = self.opcode_fetch();
-
-        let nibbles = (
-            (opcode & 0xF000) >> 12 as u8,
-            (opcode & 0x0F00) >> 8 as u8,
-            (opcode & 0x00F0) >> 4 as u8,
-            (opcode & 0x000F) as u8,
-        );
-
-        let nnn = (opcode & 0x0FFF) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        let x = nibbles.1 as usize;
-        let y = nibbles.2 as usize;
-        let n = nibbles.3 as usize;
-
-
-        let pc_change = match nibbles {
-            (0x00, 0x00, 0x0e, 0x00) => self.opcode_00e0(),
-            (0x00, 0x00, 0x0e, 0x0e) => self.opcode_00ee(),
-            (0x01, _, _, _) => self.opcode_1nnn(nnn),
-            (0x02, _, _, _) => self.opcode_2nnn(nnn),
-            (0x03, _, _, _) => self.opcode_3xkk(x, kk),
-            (0x04, _, _, _) => self.opcode_4xkk(x, kk),
-            (0x05, _, _, 0x00) => self.opcode_5xy0(x, y),
-            (0x06, _, _, _) => self.opcode_6xkk(x, kk),
-            (0x07, _, _, _) => self.opcode_7xkk(x, kk),
-            (0x08, _, _, 0x00) => self.opcode_8xy0(x, y),
-            (0x08, _, _, 0x01) => self.opcode_8xy1(x, y),
-            (0x08, _, _, 0x02) => self.opcode_8xy2(x, y),
-            (0x08, _, _, 0x03) => self.opcode_8xy3(x, y),
-            (0x08, _, _, 0x04) => self.opcode_8xy4(x, y),
-            (0x08, _, _, 0x05) => self.opcode_8xy5(x, y),
-            (0x08, _, _, 0x06) => self.opcode_8xy6(x, y),
-            (0x08, _, _, 0x07) => self.opcode_8xy7(x, y),
-            (0x08, _, _, 0x0e) => self.opcode_8xye(x, y),
-            (0x09, _, _, 0x00) => self.opcode_9xy0(x, y),
-            (0x0a, _, _, _) => self.opcode_annn(nnn),
-            (0x0b, _, _, _) => self.opcode_bnn(nnn),
-            (0x0c, _, _, _) => self.opcode_cxkk(x, kk),
-            (0x0d, _, _, _) => self.opcode_dxyn(x, y, n),
-            (0x0e, _, 0x09, 0x0e) => self.opcode_ex9e(x),
-            (0x0e, _, 0x0a, 0x01) => self.opcode_exa1(x),
-            (0x0f, _, 0x00, 0x07) => self.opcode_fx07(x),
-            (0x0f, _, 0x00, 0x0a) => self.opcode_fx0a(x),
-            (0x0f, _, 0x01, 0x05) => self.opcode_fx15(x),
-            (0x0f, _, 0x01, 0x08) => self.opcode_fx18(x),
-            (0x0f, _, 0x01, 0x0e) => self.opcode_fx1e(x),
-            (0x0f, _, 0x02, 0x09) => self.opcode_fx29(x),
-            (0x0f, _, 0x03, 0x03) => self.opcode_fx33(x),
-            (0x0f, _, 0x05, 0x05) => self.opcode_fx55(x),
-            (0x0f, _, 0x06, 0x05) => self.opcode_fx65(x),
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
 
-            _ => ProgramCounter::Next
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: usize) {
+        self.cycles_per_frame = cycles_per_frame;
+    }
+
+    pub fn key_wait(&self) -> KeyWait {
+        self.key_wait
+    }
+
+    /// Whether the next `step_instruction` will not execute an instruction, because the program
+    /// is waiting for a key or the display, or has exited.
+    pub fn is_stalled(&self) -> bool {
+        self.exited || self.display_waiting || self.key_wait != KeyWait::Idle
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    /// The seed the CPU was created with.
+    pub fn seed(&self) -> u64 {
+        self.random.seed()
+    }
+
+    pub fn set_random_mode(&mut self, mode: RandomMode) {
+        self.random.set_mode(mode);
+    }
+
+    /// The interpreter page read by `RandomMode::CosmacVip`; see `Random::set_vip_page`.
+    pub fn set_vip_page(&mut self, page: &[u8]) {
+        self.random.set_vip_page(page);
+    }
+
+    /// The SUPER-CHIP RPL user flags, which the frontend persists between runs.
+    pub fn rpl_flags(&self) -> [u8; RPL_FLAG_COUNT] {
+        self.rpl_flags
+    }
+
+    pub fn set_rpl_flags(&mut self, rpl_flags: [u8; RPL_FLAG_COUNT]) {
+        self.rpl_flags = rpl_flags;
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn register_i(&self) -> usize {
+        self.register_i
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// The return addresses currently on the stack, oldest first.
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.stack_pointer]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn video_memory(&self) -> &Framebuffer {
+        &self.video_memory
+    }
+
+    /// Addresses written by the last instruction, in the order they were written.
+    pub fn memory_writes(&self) -> &[usize] {
+        &self.memory_writes
+    }
+
+    /// Serialises the whole machine state; configuration such as quirks is not included.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+
+        for &register in self.registers.iter() {
+            writer.u8(register);
+        }
+        writer.u32(self.register_i as u32);
+        writer.u32(self.program_counter as u32);
+        for &address in self.stack.iter() {
+            writer.u32(address as u32);
+        }
+        writer.u8(self.stack_pointer as u8);
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        writer.bytes(&self.memory);
+
+        writer.u16(self.video_memory.width() as u16);
+        writer.u16(self.video_memory.height() as u16);
+        writer.bytes(self.video_memory.pixels());
+
+        let (key_wait_register, released_key) = match self.key_wait {
+            KeyWait::Idle => (0, NO_KEY),
+            KeyWait::Press { x } => (x, NO_KEY),
+            KeyWait::Release { x, key } => (x, key as u8),
+        };
+        writer.bool(self.key_wait != KeyWait::Idle);
+        writer.u8(key_wait_register as u8);
+        writer.bool(self.display_waiting);
+        writer.bytes(&self.rpl_flags);
+        writer.u8(self.plane_mask);
+        writer.bool(self.audio_buffer.is_some());
+        writer.bytes(&self.audio_buffer.unwrap_or([0; 16]));
+        writer.u8(self.pitch);
+        writer.bool(self.exited);
+
+        let (random_state, vip_pointer, vip_value) = self.random.state();
+        writer.u64(random_state);
+        writer.u8(vip_pointer);
+        writer.u8(vip_value);
+        writer.u32(self.frame_cycles as u32);
+        writer.u8(released_key);
+
+        writer.finish()
+    }
+
+    /// Restores a state from `save_state`, leaving the CPU untouched if it is invalid.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), EmulatorError> {
+        let mut reader = StateReader::new(state)?;
+
+        let mut registers = [0; 16];
+        for register in registers.iter_mut() {
+            *register = reader.u8()?;
+        }
+        let register_i = reader.u32()? as usize;
+        let program_counter = reader.u32()? as usize;
+        let mut stack = [0; 16];
+        for address in stack.iter_mut() {
+            *address = reader.u32()? as usize;
+        }
+        let stack_pointer = reader.u8()? as usize;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let memory = reader.bytes()?;
+
+        let width = reader.u16()? as usize;
+        let height = reader.u16()? as usize;
+        let resolution = (width, height);
+        if resolution != (CHIP8_WIDTH, CHIP8_HEIGHT) && resolution != (SCHIP_WIDTH, SCHIP_HEIGHT) {
+            return Err(savestate::invalid("unsupported framebuffer size"));
+        }
+        let video_memory = Framebuffer::from_pixels(width, height, reader.bytes()?)
+            .ok_or_else(|| savestate::invalid("framebuffer size mismatch"))?;
+
+        let key_waiting = reader.bool()?;
+        let key_wait_register = reader.u8()? as usize;
+        let display_waiting = reader.bool()?;
+        let rpl_flags = reader.bytes()?;
+        let plane_mask = reader.u8()?;
+        let has_audio_buffer = reader.bool()?;
+        let audio_buffer = reader.bytes()?;
+        let pitch = reader.u8()?;
+        let exited = reader.bool()?;
+
+        let random_state = reader.u64()?;
+        let vip_pointer = reader.u8()?;
+        let vip_value = reader.u8()?;
+        let frame_cycles = reader.u32()? as usize;
+        let released_key = reader.u8()?;
+
+        if memory.len() != self.memory.len() {
+            return Err(savestate::invalid("memory size differs from the running program"));
+        }
+        if stack_pointer > stack.len() || key_wait_register > 0x0f ||
+            (released_key > 0x0f && released_key != NO_KEY) ||
+            rpl_flags.len() != RPL_FLAG_COUNT || audio_buffer.len() != 16 {
+            return Err(savestate::invalid("corrupt machine state"));
+        }
+
+        self.registers = registers;
+        self.register_i = register_i;
+        self.program_counter = program_counter;
+        self.stack = stack;
+        self.stack_pointer = stack_pointer;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.memory.copy_from_slice(memory);
+        self.video_memory = video_memory;
+        self.video_memory_changed = true;
+        self.key_wait = match (key_waiting, released_key) {
+            (false, _) => KeyWait::Idle,
+            (true, NO_KEY) => KeyWait::Press { x: key_wait_register },
+            (true, key) => KeyWait::Release { x: key_wait_register, key: key as usize },
+        };
+        self.display_waiting = display_waiting;
+        self.rpl_flags.copy_from_slice(rpl_flags);
+        self.plane_mask = plane_mask;
+        self.audio_buffer = if has_audio_buffer {
+            let mut buffer = [0; 16];
+            buffer.copy_from_slice(audio_buffer);
+            Some(buffer)
+        } else {
+            None
+        };
+        self.pitch = pitch;
+        self.exited = exited;
+        self.random.set_state(random_state, vip_pointer, vip_value);
+        self.frame_cycles = frame_cycles;
+
+        Ok(())
+    }
+
+    pub fn opcode_execute(&mut self) -> Result<(), EmulatorError> {
+
+        let opcode = self.opcode_fetch(self.program_counter)?;
+        let operand = if instruction::is_long(opcode) {
+            self.opcode_fetch(self.program_counter + OPCODE_SIZE)?
+        } else {
+            0
+        };
+
+        let instruction = instruction::decode(opcode, operand);
+
+        let pc_change = match instruction {
+            Instruction::ScrollDown(n) => self.opcode_00cn(n),
+            Instruction::ScrollUp(n) => self.opcode_00dn(n),
+            Instruction::Clear => self.opcode_00e0(),
+            Instruction::Return => self.opcode_00ee()?,
+            Instruction::ScrollRight => self.opcode_00fb(),
+            Instruction::ScrollLeft => self.opcode_00fc(),
+            Instruction::Exit => self.opcode_00fd(),
+            Instruction::LowRes => self.opcode_00fe(),
+            Instruction::HighRes => self.opcode_00ff(),
+            Instruction::Jump(nnn) => self.opcode_1nnn(nnn),
+            Instruction::Call(nnn) => self.opcode_2nnn(nnn)?,
+            Instruction::SkipEqualByte(x, kk) => self.opcode_3xkk(x, kk),
+            Instruction::SkipNotEqualByte(x, kk) => self.opcode_4xkk(x, kk),
+            Instruction::SkipEqual(x, y) => self.opcode_5xy0(x, y),
+            Instruction::SaveRange(x, y) => self.opcode_5xy2(x, y)?,
+            Instruction::LoadRange(x, y) => self.opcode_5xy3(x, y)?,
+            Instruction::LoadByte(x, kk) => self.opcode_6xkk(x, kk),
+            Instruction::AddByte(x, kk) => self.opcode_7xkk(x, kk),
+            Instruction::Move(x, y) => self.opcode_8xy0(x, y),
+            Instruction::Or(x, y) => self.opcode_8xy1(x, y),
+            Instruction::And(x, y) => self.opcode_8xy2(x, y),
+            Instruction::Xor(x, y) => self.opcode_8xy3(x, y),
+            Instruction::Add(x, y) => self.opcode_8xy4(x, y),
+            Instruction::Sub(x, y) => self.opcode_8xy5(x, y),
+            Instruction::ShiftRight(x, y) => self.opcode_8xy6(x, y),
+            Instruction::SubReverse(x, y) => self.opcode_8xy7(x, y),
+            Instruction::ShiftLeft(x, y) => self.opcode_8xye(x, y),
+            Instruction::SkipNotEqual(x, y) => self.opcode_9xy0(x, y),
+            Instruction::LoadI(nnn) => self.opcode_annn(nnn),
+            Instruction::JumpOffset(x, nnn) => self.opcode_bnn(x, nnn),
+            Instruction::Random(x, kk) => self.opcode_cxkk(x, kk),
+            Instruction::Draw(x, y, n) => self.opcode_dxyn(x, y, n)?,
+            Instruction::SkipKeyPressed(x) => self.opcode_ex9e(x),
+            Instruction::SkipKeyNotPressed(x) => self.opcode_exa1(x),
+            Instruction::LoadLongI(nnnn) => self.opcode_f000(nnnn),
+            Instruction::SelectPlanes(n) => self.opcode_fn01(n),
+            Instruction::LoadAudio => self.opcode_f002()?,
+            Instruction::GetDelay(x) => self.opcode_fx07(x),
+            Instruction::WaitKey(x) => self.opcode_fx0a(x),
+            Instruction::SetDelay(x) => self.opcode_fx15(x),
+            Instruction::SetSound(x) => self.opcode_fx18(x),
+            Instruction::AddI(x) => self.opcode_fx1e(x),
+            Instruction::Font(x) => self.opcode_fx29(x),
+            Instruction::BigFont(x) => self.opcode_fx30(x),
+            Instruction::Bcd(x) => self.opcode_fx33(x)?,
+            Instruction::SetPitch(x) => self.opcode_fx3a(x),
+            Instruction::Store(x) => self.opcode_fx55(x)?,
+            Instruction::Load(x) => self.opcode_fx65(x)?,
+            Instruction::SaveFlags(x) => self.opcode_fx75(x),
+            Instruction::LoadFlags(x) => self.opcode_fx85(x),
+            Instruction::Unknown(opcode) => self.opcode_unknown(opcode)?,
         };
 
+        if self.timing == Timing::CosmacVip {
+            let skipped = matches!(pc_change, ProgramCounter::Skip);
+            self.frame_cycles += instruction.vip_cycles(skipped);
+        }
+
         match pc_change {
             ProgramCounter::Next => self.program_counter += OPCODE_SIZE,
-            ProgramCounter::Skip => self.program_counter += 2 * OPCODE_SIZE,
+            ProgramCounter::Skip => self.program_counter += self.skip_size(),
             ProgramCounter::Jump(address) => self.program_counter = address
         }
+        Ok(())
     }
 
-    fn opcode_fetch(&mut self) -> u16 {
-        return (self.memory[self.program_counter] as u16) << 8 |
-               (self.memory[self.program_counter + 1] as u16);
+    /// Skips also step over the operand of a following four-byte `F000 NNNN`.
+    fn skip_size(&self) -> usize {
+        match instruction::decode_at(&self.memory, self.program_counter + OPCODE_SIZE) {
+            Some(next) => OPCODE_SIZE + next.size(),
+            None => 2 * OPCODE_SIZE,
+        }
     }
 
-    fn opcode_00e0(&mut self) -> ProgramCounter {
-        for y in 0..CHIP8_HEIGHT {
-            for x in 0..CHIP8_WIDTH {
-                self.video_memory[y][x] = 0;
-            }
+    fn opcode_fetch(&self, address: usize) -> Result<u16, EmulatorError> {
+        Ok((self.read_memory(address)? as u16) << 8 |
+           (self.read_memory(address + 1)? as u16))
+    }
+
+    fn read_memory(&self, address: usize) -> Result<u8, EmulatorError> {
+        self.memory.get(address).cloned()
+            .ok_or(EmulatorError::MemoryOutOfRange { address })
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), EmulatorError> {
+        let byte = self.memory.get_mut(address)
+            .ok_or(EmulatorError::MemoryOutOfRange { address })?;
+        *byte = value;
+        self.memory_writes.push(address);
+        Ok(())
+    }
+
+    fn opcode_unknown(&self, opcode: u16) -> Result<ProgramCounter, EmulatorError> {
+        let error = EmulatorError::UnknownOpcode { address: self.program_counter, opcode };
+
+        match self.strictness {
+            Strictness::Ignore => {}
+            Strictness::Log => eprintln!("Warning: {}", error),
+            Strictness::Halt => return Err(error),
         }
+        Ok(ProgramCounter::Next)
+    }
+
+    fn opcode_00cn(&mut self, n: usize) -> ProgramCounter {
+        self.video_memory.scroll_down(n, self.plane_mask);
         self.video_memory_changed = true;
         ProgramCounter::Next
     }
 
-    fn opcode_00ee(&mut self) -> ProgramCounter {
+    fn opcode_00dn(&mut self, n: usize) -> ProgramCounter {
+        self.video_memory.scroll_up(n, self.plane_mask);
+        self.video_memory_changed = true;
+        ProgramCounter::Next
+    }
+
+    fn opcode_00e0(&mut self) -> ProgramCounter {
+        self.video_memory.clear_planes(self.plane_mask);
+        self.video_memory_changed = true;
+        ProgramCounter::Next
+    }
+
+    fn opcode_00ee(&mut self) -> Result<ProgramCounter, EmulatorError> {
+        if self.stack_pointer == 0 {
+            return Err(EmulatorError::StackUnderflow { address: self.program_counter });
+        }
+
         self.stack_pointer -= 1;
-        ProgramCounter::Jump(self.stack[self.stack_pointer])
+        Ok(ProgramCounter::Jump(self.stack[self.stack_pointer]))
+    }
+
+    fn opcode_00fb(&mut self) -> ProgramCounter {
+        self.video_memory.scroll_right(4, self.plane_mask);
+        self.video_memory_changed = true;
+        ProgramCounter::Next
+    }
+
+    fn opcode_00fc(&mut self) -> ProgramCounter {
+        self.video_memory.scroll_left(4, self.plane_mask);
+        self.video_memory_changed = true;
+        ProgramCounter::Next
+    }
+
+    fn opcode_00fd(&mut self) -> ProgramCounter {
+        self.exited = true;
+        ProgramCounter::Next
+    }
+
+    fn opcode_00fe(&mut self) -> ProgramCounter {
+        self.video_memory.resize(CHIP8_WIDTH, CHIP8_HEIGHT);
+        self.video_memory_changed = true;
+        ProgramCounter::Next
+    }
+
+    fn opcode_00ff(&mut self) -> ProgramCounter {
+        self.video_memory.resize(SCHIP_WIDTH, SCHIP_HEIGHT);
+        self.video_memory_changed = true;
+        ProgramCounter::Next
     }
 
     fn opcode_1nnn(&self, nnn: usize) -> ProgramCounter {
         ProgramCounter::Jump(nnn)
     }
 
-    fn opcode_2nnn(&mut self, nnn: usize) -> ProgramCounter {
+    fn opcode_2nnn(&mut self, nnn: usize) -> Result<ProgramCounter, EmulatorError> {
+        if self.stack_pointer == self.stack.len() {
+            return Err(EmulatorError::StackOverflow { address: self.program_counter });
+        }
+
         self.stack[self.stack_pointer] = self.program_counter + OPCODE_SIZE;
         self.stack_pointer += 1;
 
-        ProgramCounter::Jump(nnn)
+        Ok(ProgramCounter::Jump(nnn))
     }
 
     fn opcode_3xkk(&self, x: usize, kk: u8) -> ProgramCounter {
@@ -206,6 +694,24 @@ impl CPU {
         ProgramCounter::skip_if(self.registers[x] == self.registers[y])
     }
 
+    fn opcode_5xy2(&mut self, x: usize, y: usize) -> Result<ProgramCounter, EmulatorError> {
+        for offset in 0..=x.abs_diff(y) {
+            let register = if x <= y { x + offset } else { x - offset };
+            self.write_memory(self.register_i + offset, self.registers[register])?;
+        }
+
+        Ok(ProgramCounter::Next)
+    }
+
+    fn opcode_5xy3(&mut self, x: usize, y: usize) -> Result<ProgramCounter, EmulatorError> {
+        for offset in 0..=x.abs_diff(y) {
+            let register = if x <= y { x + offset } else { x - offset };
+            self.registers[register] = self.read_memory(self.register_i + offset)?;
+        }
+
+        Ok(ProgramCounter::Next)
+    }
+
     fn opcode_6xkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
         self.registers[x] = kk;
 
@@ -230,54 +736,74 @@ impl CPU {
     fn opcode_8xy1(&mut self, x: usize, y: usize) -> ProgramCounter {
         self.registers[x] |= self.registers[y];
 
+        if self.quirks.logic_resets_vf {
+            self.registers[0x0f] = 0;
+        }
+
         ProgramCounter::Next
     }
 
     fn opcode_8xy2(&mut self, x: usize, y: usize) -> ProgramCounter {
         self.registers[x] &= self.registers[y];
 
+        if self.quirks.logic_resets_vf {
+            self.registers[0x0f] = 0;
+        }
+
         ProgramCounter::Next
     }
 
     fn opcode_8xy3(&mut self, x: usize, y: usize) -> ProgramCounter {
         self.registers[x] ^= self.registers[y];
 
+        if self.quirks.logic_resets_vf {
+            self.registers[0x0f] = 0;
+        }
+
         ProgramCounter::Next
     }
 
     fn opcode_8xy4(&mut self, x: usize, y: usize) -> ProgramCounter {
-        let result = (self.registers[x] as u16) + (self.registers[y] as u16);
-        self.registers[x] = result as u8;
+        let (result, carry) = self.registers[x].overflowing_add(self.registers[y]);
 
-        self.registers[0x0f] = if result > 0xFF { 1 } else { 0 };
+        self.registers[x] = result;
+        self.registers[0x0f] = carry as u8;
 
         ProgramCounter::Next
     }
 
     fn opcode_8xy5(&mut self, x: usize, y: usize) -> ProgramCounter {
-         self.registers[0x0f] = if self.registers[x] > self.registers[y] { 1 } else { 0 };
-         self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
+        let no_borrow = self.registers[x] >= self.registers[y];
+
+        self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
+        self.registers[0x0f] = no_borrow as u8;
 
         ProgramCounter::Next
     }
 
     fn opcode_8xy6(&mut self, x: usize, y: usize) -> ProgramCounter {
-         self.registers[0x0f] =  self.registers[x] & 1;
-         self.registers[x] /= 2;
+        let source = if self.quirks.shift_uses_vy { self.registers[y] } else { self.registers[x] };
+
+        self.registers[x] = source >> 1;
+        self.registers[0x0f] = source & 1;
 
         ProgramCounter::Next
     }
 
     fn opcode_8xy7(&mut self, x: usize, y: usize) -> ProgramCounter {
-        self.registers[0x0f] = if self.registers[y] > self.registers[x] { 1 } else { 0 };
+        let no_borrow = self.registers[y] >= self.registers[x];
+
         self.registers[x] = self.registers[y].wrapping_sub(self.registers[x]);
+        self.registers[0x0f] = no_borrow as u8;
 
         ProgramCounter::Next
     }
 
     fn opcode_8xye(&mut self, x: usize, y: usize) -> ProgramCounter {
-        self.registers[0x0f] = (self.registers[x] & 0b10000000) >> 7;
-        self.registers[x] <<= 1;
+        let source = if self.quirks.shift_uses_vy { self.registers[y] } else { self.registers[x] };
+
+        self.registers[x] = source << 1;
+        self.registers[0x0f] = source >> 7;
 
         ProgramCounter::Next
     }
@@ -292,39 +818,107 @@ impl CPU {
         ProgramCounter::Next
     }
 
-    fn opcode_bnn(&self, nnn: usize) ->  ProgramCounter {
-        ProgramCounter::Jump((self.registers[0] as usize) + nnn)
+    fn opcode_bnn(&self, x: usize, nnn: usize) ->  ProgramCounter {
+        let register = if self.quirks.jump_uses_vx { x } else { 0 };
+        ProgramCounter::Jump((self.registers[register] as usize) + nnn)
     }
 
     fn opcode_cxkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
-        let mut range = rand::thread_rng();
-        self.registers[x] = range.gen::<u8>() & kk;
+        self.registers[x] = self.random.next_byte() & kk;
 
         ProgramCounter::Next
     }
 
-    fn opcode_dxyn(&mut self, x: usize, y: usize, n: usize) -> ProgramCounter {
+    fn opcode_dxyn(&mut self, x: usize, y: usize, n: usize) -> Result<ProgramCounter, EmulatorError> {
+        let width = self.video_memory.width();
+        let height = self.video_memory.height();
+        let origin_x = self.registers[x] as usize % width;
+        let origin_y = self.registers[y] as usize % height;
+
+        // DXY0 draws a 16x16 sprite stored as two bytes per row.
+        let (sprite_width, rows) = if n == 0 { (16, 16) } else { (8, n) };
+        let bytes_per_row = sprite_width / 8;
+
+        // Each selected plane takes its own copy of the sprite data, one after the other.
+        let mut sprite_address = self.register_i;
+
         self.registers[0x0f] = 0;
-        for byte in 0..n  {
-            let y = (self.registers[y] as usize + byte) % CHIP8_HEIGHT;
-                for bit in 0..8 {
-                let x = (self.registers[x] as usize + bit) % CHIP8_WIDTH;
-                let color = (self.memory[self.register_i + byte] >> (7 - bit)) & 1;
-                self.registers[0x0f] |= color & self.video_memory[y][x];
-                self.video_memory[y][x] ^= color;
+        for plane in 0..PLANE_COUNT {
+            let plane_bit = 1 << plane;
+            if self.plane_mask & plane_bit == 0 {
+                continue;
+            }
+
+            for row in 0..rows  {
+                let y = origin_y + row;
+                if self.quirks.clip_sprites && y >= height {
+                    break;
+                }
+                let y = y % height;
+
+                let address = sprite_address + row * bytes_per_row;
+                let sprite_row = if bytes_per_row == 2 {
+                    (self.read_memory(address)? as u16) << 8 | self.read_memory(address + 1)? as u16
+                } else {
+                    (self.read_memory(address)? as u16) << 8
+                };
+
+                for bit in 0..sprite_width {
+                    let x = origin_x + bit;
+                    if self.quirks.clip_sprites && x >= width {
+                        break;
+                    }
+                    let x = x % width;
+                    let color = if (sprite_row >> (15 - bit)) & 1 == 1 { plane_bit } else { 0 };
+                    let pixel = self.video_memory.get(x, y);
+                    if color & pixel != 0 {
+                        self.registers[0x0f] = 1;
+                    }
+                    self.video_memory.set(x, y, pixel ^ color);
+                }
             }
+
+            sprite_address += rows * bytes_per_row;
         }
 
         self.video_memory_changed = true;
-        ProgramCounter::Next
+        self.display_waiting = self.quirks.display_wait || self.timing == Timing::CosmacVip;
+        Ok(ProgramCounter::Next)
     }
 
     fn opcode_ex9e(&self, x: usize) -> ProgramCounter {
-        ProgramCounter::skip_if(self.keypad[self.registers[x] as usize])
+        ProgramCounter::skip_if(self.key_in(x))
     }
 
     fn opcode_exa1(&self, x: usize) -> ProgramCounter {
-        ProgramCounter::skip_if(!self.keypad[self.registers[x] as usize])
+        ProgramCounter::skip_if(!self.key_in(x))
+    }
+
+    /// Whether the key named by Vx is held. Only the low nibble is used, as on the VIP.
+    fn key_in(&self, x: usize) -> bool {
+        self.keypad[(self.registers[x] & 0x0f) as usize]
+    }
+
+    fn opcode_f000(&mut self, nnnn: usize) -> ProgramCounter {
+        self.register_i = nnnn;
+
+        ProgramCounter::Jump(self.program_counter + 2 * OPCODE_SIZE)
+    }
+
+    fn opcode_fn01(&mut self, n: usize) -> ProgramCounter {
+        self.plane_mask = n as u8 & 0b11;
+
+        ProgramCounter::Next
+    }
+
+    fn opcode_f002(&mut self) -> Result<ProgramCounter, EmulatorError> {
+        let mut buffer = [0; 16];
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_memory(self.register_i + i)?;
+        }
+        self.audio_buffer = Some(buffer);
+
+        Ok(ProgramCounter::Next)
     }
 
     fn opcode_fx07(&mut self, x: usize) -> ProgramCounter {
@@ -334,8 +928,7 @@ impl CPU {
     }
 
     fn opcode_fx0a(&mut self, x: usize) -> ProgramCounter {
-        self.keypad_waiting = true;
-        self.keypad_register = x;
+        self.key_wait = KeyWait::Press { x };
 
         ProgramCounter::Next
     }
@@ -353,7 +946,7 @@ impl CPU {
     }
 
     fn opcode_fx1e(&mut self, x: usize) -> ProgramCounter {
-        self.register_i += (self.registers[x] as usize);
+        self.register_i += self.registers[x] as usize;
         self.registers[0x0f] = if self.register_i > 0xF00 { 1 } else { 0 };
 
         ProgramCounter::Next
@@ -365,25 +958,323 @@ impl CPU {
         ProgramCounter::Next
     }
 
-    fn opcode_fx33(&mut self, x: usize) -> ProgramCounter {
-        self.memory[self.register_i] = self.registers[x] / 100;
-        self.memory[self.register_i + 1] = (self.registers[x] % 100) / 10;
-        self.memory[self.register_i + 2] = self.registers[x] % 10;
+    fn opcode_fx30(&mut self, x: usize) -> ProgramCounter {
+        self.register_i = BIG_FONT_ADDRESS + (self.registers[x] as usize & 0x0f) * 10;
 
         ProgramCounter::Next
     }
 
-    fn opcode_fx55(&mut self, x: usize) -> ProgramCounter {
+    fn opcode_fx33(&mut self, x: usize) -> Result<ProgramCounter, EmulatorError> {
+        self.write_memory(self.register_i, self.registers[x] / 100)?;
+        self.write_memory(self.register_i + 1, (self.registers[x] % 100) / 10)?;
+        self.write_memory(self.register_i + 2, self.registers[x] % 10)?;
+
+        Ok(ProgramCounter::Next)
+    }
+
+    fn opcode_fx3a(&mut self, x: usize) -> ProgramCounter {
+        self.pitch = self.registers[x];
+
+        ProgramCounter::Next
+    }
+
+    fn opcode_fx55(&mut self, x: usize) -> Result<ProgramCounter, EmulatorError> {
         for i in 0..x + 1 {
-            self.memory[self.register_i + i] = self.registers[i];
+            self.write_memory(self.register_i + i, self.registers[i])?;
         }
-        ProgramCounter::Next
+
+        self.advance_i_after_load_store(x);
+        Ok(ProgramCounter::Next)
     }
 
-    fn opcode_fx65(&mut self, x: usize) -> ProgramCounter {
+    fn opcode_fx65(&mut self, x: usize) -> Result<ProgramCounter, EmulatorError> {
         for i in 0..x + 1  {
-            self.registers[i] = self.memory[self.register_i + i];
+            self.registers[i] = self.read_memory(self.register_i + i)?;
+        }
+
+        self.advance_i_after_load_store(x);
+        Ok(ProgramCounter::Next)
+    }
+
+    fn advance_i_after_load_store(&mut self, x: usize) {
+        if self.quirks.load_store_increments_i {
+            self.register_i += if self.quirks.load_store_increments_by_x { x } else { x + 1 };
+        }
+    }
+
+    fn opcode_fx75(&mut self, x: usize) -> ProgramCounter {
+        for i in 0..(x + 1).min(RPL_FLAG_COUNT) {
+            self.rpl_flags[i] = self.registers[i];
+        }
+        ProgramCounter::Next
+    }
+
+    fn opcode_fx85(&mut self, x: usize) -> ProgramCounter {
+        for i in 0..(x + 1).min(RPL_FLAG_COUNT) {
+            self.registers[i] = self.rpl_flags[i];
         }
         ProgramCounter::Next
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::RAM;
+
+    /// Runs a single `8XYN` instruction with the given registers set beforehand.
+    fn alu(quirks: Quirks, opcode: u16, setup: &[(usize, u8)]) -> CPU {
+        let mut memory = RAM::new().memory;
+        memory[PROGRAM_START] = (opcode >> 8) as u8;
+        memory[PROGRAM_START + 1] = opcode as u8;
+
+        let mut cpu = CPU::new(memory, quirks, 0);
+        for &(register, value) in setup {
+            cpu.registers[register] = value;
+        }
+        cpu.opcode_execute().unwrap();
+        cpu
+    }
+
+    #[test]
+    fn opcode_8xy0_copies_vy() {
+        let cpu = alu(Quirks::default(), 0x8AB0, &[(0xa, 1), (0xb, 0x42)]);
+        assert_eq!(cpu.registers[0xa], 0x42);
+    }
+
+    #[test]
+    fn logic_opcodes_reset_vf_only_with_the_quirk() {
+        let cases = [(0x8AB1, 0x3f), (0x8AB2, 0x0c), (0x8AB3, 0x33)];
+
+        for &(opcode, expected) in cases.iter() {
+            let cpu = alu(Quirks::cosmac_vip(), opcode, &[(0xa, 0x3c), (0xb, 0x0f), (0xf, 5)]);
+            assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (expected, 0));
+
+            let cpu = alu(Quirks::super_chip(), opcode, &[(0xa, 0x3c), (0xb, 0x0f), (0xf, 5)]);
+            assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (expected, 5));
+        }
+    }
+
+    #[test]
+    fn opcode_8xy4_sets_carry() {
+        let cpu = alu(Quirks::default(), 0x8AB4, &[(0xa, 0xf0), (0xb, 0x20)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x10, 1));
+
+        let cpu = alu(Quirks::default(), 0x8AB4, &[(0xa, 0x01), (0xb, 0x01)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x02, 0));
+    }
+
+    #[test]
+    fn opcode_8xy4_writes_vf_last() {
+        let cpu = alu(Quirks::default(), 0x8FB4, &[(0xf, 0xff), (0xb, 0x02)]);
+        assert_eq!(cpu.registers[0xf], 1);
+
+        let cpu = alu(Quirks::default(), 0x8AF4, &[(0xa, 0xff), (0xf, 0x01)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x00, 1));
+    }
+
+    #[test]
+    fn opcode_8xy5_sets_no_borrow_when_vx_is_at_least_vy() {
+        let cpu = alu(Quirks::default(), 0x8AB5, &[(0xa, 0x05), (0xb, 0x05)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x00, 1));
+
+        let cpu = alu(Quirks::default(), 0x8AB5, &[(0xa, 0x04), (0xb, 0x05)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0xff, 0));
+    }
+
+    #[test]
+    fn opcode_8xy5_writes_vf_last() {
+        let cpu = alu(Quirks::default(), 0x8FB5, &[(0xf, 0x10), (0xb, 0x05)]);
+        assert_eq!(cpu.registers[0xf], 1);
+
+        let cpu = alu(Quirks::default(), 0x8AF5, &[(0xa, 0x03), (0xf, 0x05)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0xfe, 0));
+    }
+
+    #[test]
+    fn opcode_8xy6_shifts_right_from_the_quirk_source() {
+        let cpu = alu(Quirks::cosmac_vip(), 0x8AB6, &[(0xa, 0x02), (0xb, 0x09)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x04, 1));
+
+        let cpu = alu(Quirks::super_chip(), 0x8AB6, &[(0xa, 0x02), (0xb, 0x09)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x01, 0));
+    }
+
+    #[test]
+    fn opcode_8xy6_writes_vf_last() {
+        let cpu = alu(Quirks::super_chip(), 0x8FB6, &[(0xf, 0x05)]);
+        assert_eq!(cpu.registers[0xf], 1);
+    }
+
+    #[test]
+    fn opcode_8xy7_sets_no_borrow_when_vy_is_at_least_vx() {
+        let cpu = alu(Quirks::default(), 0x8AB7, &[(0xa, 0x05), (0xb, 0x05)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x00, 1));
+
+        let cpu = alu(Quirks::default(), 0x8AB7, &[(0xa, 0x30), (0xb, 0x20)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0xf0, 0));
+    }
+
+    #[test]
+    fn opcode_8xy7_writes_vf_last() {
+        let cpu = alu(Quirks::default(), 0x8FB7, &[(0xf, 0x02), (0xb, 0x05)]);
+        assert_eq!(cpu.registers[0xf], 1);
+    }
+
+    #[test]
+    fn opcode_8xye_shifts_left_from_the_quirk_source() {
+        let cpu = alu(Quirks::cosmac_vip(), 0x8ABE, &[(0xa, 0x01), (0xb, 0x81)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x02, 1));
+
+        let cpu = alu(Quirks::super_chip(), 0x8ABE, &[(0xa, 0x01), (0xb, 0x81)]);
+        assert_eq!((cpu.registers[0xa], cpu.registers[0xf]), (0x02, 0));
+    }
+
+    #[test]
+    fn opcode_8xye_writes_vf_last() {
+        let cpu = alu(Quirks::super_chip(), 0x8FBE, &[(0xf, 0xc0)]);
+        assert_eq!(cpu.registers[0xf], 1);
+    }
+
+    fn keys(pressed: &[usize]) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for &key in pressed {
+            keys[key] = true;
+        }
+        keys
+    }
+
+    /// A CPU about to execute `FX0A` with X = 3.
+    fn waiting_for_key(quirks: Quirks) -> CPU {
+        let mut cpu = alu(quirks, 0xF30A, &[]);
+        cpu.set_cycles_per_frame(1);
+        cpu
+    }
+
+    #[test]
+    fn opcode_fx0a_waits_for_release_with_the_quirk() {
+        let mut cpu = waiting_for_key(Quirks::cosmac_vip());
+        assert_eq!(cpu.key_wait(), KeyWait::Press { x: 3 });
+
+        assert!(cpu.cpu_cycle(keys(&[7])).unwrap().waiting_for_key);
+        assert_eq!(cpu.key_wait(), KeyWait::Release { x: 3, key: 7 });
+
+        cpu.cpu_cycle(keys(&[7])).unwrap();
+        assert_eq!(cpu.key_wait(), KeyWait::Release { x: 3, key: 7 });
+
+        assert!(!cpu.cpu_cycle(keys(&[])).unwrap().waiting_for_key);
+        assert_eq!(cpu.registers[3], 7);
+    }
+
+    #[test]
+    fn opcode_fx0a_completes_on_press_without_the_quirk() {
+        let quirks = Quirks { wait_key_release: false, ..Quirks::cosmac_vip() };
+        let mut cpu = waiting_for_key(quirks);
+
+        cpu.cpu_cycle(keys(&[])).unwrap();
+        assert_eq!(cpu.key_wait(), KeyWait::Press { x: 3 });
+
+        cpu.cpu_cycle(keys(&[0xc])).unwrap();
+        assert_eq!(cpu.key_wait(), KeyWait::Idle);
+        assert_eq!(cpu.registers[3], 0xc);
+    }
+
+    #[test]
+    fn stalled_while_waiting_for_a_key_or_the_display() {
+        let mut cpu = waiting_for_key(Quirks::cosmac_vip());
+        assert!(cpu.is_stalled());
+        cpu.cpu_cycle(keys(&[])).unwrap();
+        assert!(cpu.is_stalled());
+
+        let mut cpu = alu(Quirks::cosmac_vip(), 0xD015, &[]);
+        assert!(cpu.is_stalled());
+        cpu.cpu_cycle(keys(&[])).unwrap();
+        assert!(!cpu.is_stalled());
+
+        assert!(!alu(Quirks::super_chip(), 0xD015, &[]).is_stalled());
+        assert!(alu(Quirks::super_chip(), 0x00FD, &[]).is_stalled());
+    }
+
+    #[test]
+    fn timers_run_while_waiting_for_a_key() {
+        let mut cpu = waiting_for_key(Quirks::cosmac_vip());
+        cpu.delay_timer = 10;
+        cpu.sound_timer = 10;
+
+        for _ in 0..4 {
+            cpu.cpu_cycle(keys(&[])).unwrap();
+        }
+        assert_eq!((cpu.delay_timer, cpu.sound_timer), (6, 6));
+    }
+
+    #[test]
+    fn exit_ends_the_frame_with_fixed_timing() {
+        let mut cpu = alu(Quirks::super_chip(), 0x00FD, &[]);
+        assert_eq!(cpu.timing, Timing::Fixed);
+        assert!(cpu.frame_finished());
+
+        let output = cpu.cpu_cycle(keys(&[])).unwrap();
+        assert!(output.exited);
+    }
+
+    #[test]
+    fn key_skips_use_the_low_nibble_of_vx() {
+        let mut cpu = alu(Quirks::default(), 0xE09E, &[(0, 0xff)]);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 2);
+
+        cpu.keypad = keys(&[0xf]);
+        cpu.program_counter = PROGRAM_START;
+        cpu.opcode_execute().unwrap();
+        assert_eq!(cpu.program_counter, PROGRAM_START + 4);
+
+        let cpu = alu(Quirks::default(), 0xE0A1, &[(0, 0x1a)]);
+        assert_eq!(cpu.program_counter, PROGRAM_START + 4);
+    }
+
+    #[test]
+    fn load_store_advances_i_per_preset() {
+        let presets = [
+            (Quirks::cosmac_vip(), 0x304),
+            (Quirks::chip48(), 0x303),
+            (Quirks::super_chip(), 0x300),
+        ];
+
+        for &(quirks, expected) in presets.iter() {
+            for &opcode in &[0xF355, 0xF365] {
+                let mut memory = RAM::new().memory;
+                memory[PROGRAM_START] = (opcode >> 8) as u8;
+                memory[PROGRAM_START + 1] = opcode as u8;
+
+                let mut cpu = CPU::new(memory, quirks, 0);
+                cpu.register_i = 0x300;
+                cpu.opcode_execute().unwrap();
+                assert_eq!(cpu.register_i, expected, "{:04X} with {:?}", opcode, quirks);
+            }
+        }
+    }
+
+    /// Machine cycles charged for one instruction under VIP timing.
+    fn vip_cost(opcode: u16, setup: &[(usize, u8)]) -> usize {
+        let mut cpu = alu(Quirks::cosmac_vip(), 0x0000, &[]);
+        cpu.set_timing(Timing::CosmacVip);
+        cpu.program_counter = PROGRAM_START;
+        cpu.memory[PROGRAM_START] = (opcode >> 8) as u8;
+        cpu.memory[PROGRAM_START + 1] = opcode as u8;
+        for &(register, value) in setup {
+            cpu.registers[register] = value;
+        }
+
+        cpu.frame_cycles = 0;
+        cpu.opcode_execute().unwrap();
+        cpu.frame_cycles
+    }
+
+    #[test]
+    fn vip_timing_charges_per_instruction() {
+        assert!(vip_cost(0xF555, &[]) > vip_cost(0xF055, &[]));
+        assert_eq!(vip_cost(0xF565, &[]) - vip_cost(0xF065, &[]), 5 * 14);
+        assert!(vip_cost(0x3012, &[(0, 0x12)]) > vip_cost(0x3012, &[]));
+        assert!(vip_cost(0x00E0, &[]) > 3000);
+    }
+}
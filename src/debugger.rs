@@ -0,0 +1,381 @@
+use std::io;
+use std::io::prelude::*;
+
+use cpu::{KeyWait, CPU};
+use error::EmulatorError;
+use frontend::{self, AudioSink, FrameClock, KeypadSource, VideoSink};
+use instruction;
+
+/// Instructions shown either side of the program counter by `list`.
+const LIST_CONTEXT: usize = 5;
+
+const HELP: &str = "\
+commands (numbers are decimal, or hex with a 0x prefix):
+  c, continue             run until a breakpoint or watchpoint
+  s, step [count]         execute one or more instructions
+  f, frame                run to the end of the current frame
+  b, break ADDR [if REG OP VALUE]
+                          break at ADDR, optionally only when e.g. `V3 == 0x10` or `I > 0x300`
+  d, delete ADDR          remove the breakpoints at ADDR
+  w, watch ADDR           break after an instruction writes to ADDR
+  u, unwatch ADDR         remove a watchpoint
+  r, regs                 show registers, stack and timers
+  l, list                 disassemble around the program counter
+  x ADDR [count]          dump memory
+  q, quit                 stop the emulator";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Register {
+    V(usize),
+    I,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Condition {
+    register: Register,
+    comparison: Comparison,
+    value: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Breakpoint {
+    address: usize,
+    condition: Option<Condition>,
+}
+
+enum Command {
+    Continue,
+    Step(usize),
+    Frame,
+    Quit,
+}
+
+/// Breakpoints and watchpoints set from the REPL.
+#[derive(Default)]
+struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<usize>,
+}
+
+/// Runs the program under the debugger, starting paused at the first instruction.
+pub fn run<V, A, K>(cpu: &mut CPU, video: &mut V, audio: &mut A, keypad: &mut K)
+    -> Result<(), EmulatorError>
+    where V: VideoSink, A: AudioSink, K: KeypadSource {
+
+    let mut debugger = Debugger::default();
+    let mut clock = FrameClock::new(video.paces_frames());
+    let mut audio_pattern = None;
+
+    let mut paused = true;
+    let mut steps_remaining = None;
+    let mut stop_at_frame_end = false;
+    let mut resumed = false;
+
+    while let Some(keys) = keypad.poll() {
+        cpu.begin_frame(keys);
+
+        while !cpu.frame_finished() {
+            // Breakpoints and steps only count instructions that execute, not a stalled CPU
+            // sitting on the same one.
+            let stalled = cpu.is_stalled();
+
+            if !stalled {
+                // Don't stop again on the breakpoint we have just resumed from.
+                if !paused && !resumed && debugger.breakpoint_hit(cpu) {
+                    println!("breakpoint at {:#05X}", cpu.program_counter());
+                    paused = true;
+                }
+                resumed = false;
+            }
+
+            if paused {
+                video.draw(cpu.video_memory());
+                video.present();
+                audio.stop_beep();
+
+                match debugger.prompt(cpu) {
+                    Command::Continue => {}
+                    Command::Step(count) => steps_remaining = Some(count),
+                    Command::Frame => stop_at_frame_end = true,
+                    Command::Quit => return Ok(()),
+                }
+                paused = false;
+                resumed = true;
+            }
+
+            cpu.step_instruction()?;
+
+            if let Some(address) = debugger.watchpoint_hit(cpu) {
+                println!("watchpoint: {:#05X} written, now {:#04X}", address, cpu.memory()[address]);
+                paused = true;
+            }
+
+            if let (Some(count), false) = (steps_remaining, stalled) {
+                if count <= 1 {
+                    steps_remaining = None;
+                    paused = true;
+                } else {
+                    steps_remaining = Some(count - 1);
+                }
+            }
+        }
+
+        let output = cpu.output();
+        frontend::present(&output, video, audio, &mut audio_pattern);
+
+        if output.exited {
+            println!("program exited");
+            break;
+        }
+
+        if stop_at_frame_end {
+            stop_at_frame_end = false;
+            paused = true;
+        }
+
+        if !paused {
+            clock.wait();
+        }
+    }
+
+    Ok(())
+}
+
+impl Debugger {
+    fn breakpoint_hit(&self, cpu: &CPU) -> bool {
+        self.breakpoints.iter().any(|breakpoint| {
+            breakpoint.address == cpu.program_counter() &&
+                breakpoint.condition.is_none_or(|condition| condition.holds(cpu))
+        })
+    }
+
+    fn watchpoint_hit(&self, cpu: &CPU) -> Option<usize> {
+        cpu.memory_writes().iter().cloned().find(|address| self.watchpoints.contains(address))
+    }
+
+    /// Reads commands until one of them resumes execution.
+    fn prompt(&mut self, cpu: &CPU) -> Command {
+        print_location(cpu);
+
+        let stdin = io::stdin();
+        loop {
+            print!("(chip8) ");
+            let _ = io::stdout().flush();
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => return Command::Quit,
+                Ok(_) => {}
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            let outcome = match words[0] {
+                "c" | "continue" => return Command::Continue,
+                "s" | "step" => match words.get(1).map(|count| parse_number(count)) {
+                    None => return Command::Step(1),
+                    Some(Ok(count)) => return Command::Step(count.max(1)),
+                    Some(Err(message)) => Err(message),
+                },
+                "f" | "frame" => return Command::Frame,
+                "q" | "quit" => return Command::Quit,
+                "b" | "break" => self.add_breakpoint(&words[1..]),
+                "d" | "delete" => parse_address(&words).map(|address| {
+                    self.breakpoints.retain(|breakpoint| breakpoint.address != address);
+                }),
+                "w" | "watch" => parse_address(&words).map(|address| {
+                    self.watchpoints.push(address);
+                }),
+                "u" | "unwatch" => parse_address(&words).map(|address| {
+                    self.watchpoints.retain(|&watched| watched != address);
+                }),
+                "r" | "regs" => {
+                    print!("{}", registers_report(cpu));
+                    Ok(())
+                }
+                "l" | "list" => {
+                    print_listing(cpu);
+                    Ok(())
+                }
+                "x" => dump_memory(cpu, &words[1..]),
+                "h" | "help" => {
+                    println!("{}", HELP);
+                    Ok(())
+                }
+                other => Err(format!("unknown command '{}', try 'help'", other)),
+            };
+
+            if let Err(message) = outcome {
+                println!("{}", message);
+            }
+        }
+    }
+
+    /// Parses `ADDR [if REG OP VALUE]`.
+    fn add_breakpoint(&mut self, words: &[&str]) -> Result<(), String> {
+        let address = parse_number(words.first().ok_or("an address is required")?)?;
+
+        let condition = match words.get(1) {
+            None => None,
+            Some(&"if") if words.len() == 5 => Some(Condition {
+                register: parse_register(words[2])?,
+                comparison: parse_comparison(words[3])?,
+                value: parse_number(words[4])?,
+            }),
+            Some(_) => return Err("expected `if REG OP VALUE` after the address".to_string()),
+        };
+
+        self.breakpoints.push(Breakpoint { address, condition });
+        println!("breakpoint set at {:#05X}", address);
+        Ok(())
+    }
+}
+
+impl Condition {
+    fn holds(&self, cpu: &CPU) -> bool {
+        let actual = match self.register {
+            Register::V(x) => cpu.registers()[x] as usize,
+            Register::I => cpu.register_i(),
+        };
+
+        match self.comparison {
+            Comparison::Equal => actual == self.value,
+            Comparison::NotEqual => actual != self.value,
+            Comparison::Less => actual < self.value,
+            Comparison::LessOrEqual => actual <= self.value,
+            Comparison::Greater => actual > self.value,
+            Comparison::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+/// Parses the address argument of a one-address command.
+fn parse_address(words: &[&str]) -> Result<usize, String> {
+    parse_number(words.get(1).ok_or("an address is required")?)
+}
+
+fn parse_number(word: &str) -> Result<usize, String> {
+    let parsed = if word.starts_with("0x") || word.starts_with("0X") {
+        usize::from_str_radix(&word[2..], 16)
+    } else {
+        word.parse()
+    };
+    parsed.map_err(|_| format!("'{}' is not a number", word))
+}
+
+fn parse_register(word: &str) -> Result<Register, String> {
+    let upper = word.to_uppercase();
+    if upper == "I" {
+        return Ok(Register::I);
+    }
+
+    let digit = upper.strip_prefix('V').filter(|digit| digit.len() == 1);
+    match digit.map(|digit| usize::from_str_radix(digit, 16)) {
+        Some(Ok(x)) => Ok(Register::V(x)),
+        _ => Err(format!("'{}' is not a register, expected V0-VF or I", word)),
+    }
+}
+
+fn parse_comparison(word: &str) -> Result<Comparison, String> {
+    match word {
+        "==" => Ok(Comparison::Equal),
+        "!=" => Ok(Comparison::NotEqual),
+        "<" => Ok(Comparison::Less),
+        "<=" => Ok(Comparison::LessOrEqual),
+        ">" => Ok(Comparison::Greater),
+        ">=" => Ok(Comparison::GreaterOrEqual),
+        _ => Err(format!("'{}' is not a comparison", word)),
+    }
+}
+
+fn print_location(cpu: &CPU) {
+    match cpu.key_wait() {
+        KeyWait::Idle => {}
+        KeyWait::Press { x } => println!("waiting for a key press for V{:X}", x),
+        KeyWait::Release { x, key } => println!("waiting for key {:X} to be released for V{:X}", key, x),
+    }
+
+    match instruction::decode_at(cpu.memory(), cpu.program_counter()) {
+        Some(next) => println!("{:04X}  {}", cpu.program_counter(), next),
+        None => println!("{:04X}  <out of memory>", cpu.program_counter()),
+    }
+}
+
+/// Registers, stack and timers, one line each; shared with the headless runner.
+pub(crate) fn registers_report(cpu: &CPU) -> String {
+    let registers: Vec<String> = cpu.registers().iter().enumerate()
+        .map(|(x, value)| format!("V{:X} {:02X}", x, value))
+        .collect();
+    let stack: Vec<String> = cpu.stack().iter().map(|address| format!("{:#05X}", address)).collect();
+
+    format!("PC {:#05X}  I {:#05X}  DT {:3}  ST {:3}\n{}\n{}\nstack [{}]\n",
+            cpu.program_counter(), cpu.register_i(), cpu.delay_timer(), cpu.sound_timer(),
+            registers[..8].join("  "), registers[8..].join("  "), stack.join(", "))
+}
+
+/// Decodes linearly from a few instructions before PC, which may misalign on data but is
+/// right for straight-line code.
+fn print_listing(cpu: &CPU) {
+    let pc = cpu.program_counter();
+    let mut address = pc.saturating_sub(LIST_CONTEXT * instruction::OPCODE_SIZE);
+
+    for _ in 0..2 * LIST_CONTEXT + 1 {
+        let next = match instruction::decode_at(cpu.memory(), address) {
+            Some(next) => next,
+            None => break,
+        };
+
+        let marker = if address == pc { "=>" } else { "  " };
+        println!("{} {:04X}  {}", marker, address, next);
+        address += next.size();
+    }
+}
+
+fn dump_memory(cpu: &CPU, words: &[&str]) -> Result<(), String> {
+    let start = parse_number(words.first().ok_or("an address is required")?)?;
+    let count = match words.get(1) {
+        Some(count) => parse_number(count)?,
+        None => 16,
+    };
+
+    let end = start.checked_add(count).ok_or("address is out of range")?.min(cpu.memory().len());
+    let memory = cpu.memory().get(start..end).ok_or("address is out of range")?;
+
+    for (row, bytes) in memory.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        println!("{:04X}  {}", start + row * 16, hex.join(" "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_registers() {
+        assert_eq!(parse_register("v3"), Ok(Register::V(3)));
+        assert_eq!(parse_register("VF"), Ok(Register::V(0xf)));
+        assert_eq!(parse_register("i"), Ok(Register::I));
+    }
+
+    #[test]
+    fn rejects_other_words_as_registers() {
+        for word in &["é", "Vé", "éV", "V10", "VG", "V", ""] {
+            assert!(parse_register(word).is_err(), "{}", word);
+        }
+    }
+}
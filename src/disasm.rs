@@ -0,0 +1,219 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use instruction::{self, Instruction, OPCODE_SIZE};
+use memory::PROGRAM_START;
+
+/// Disassembles a ROM loaded at `PROGRAM_START`.
+///
+/// Code is found by following control flow from the entry point, so bytes that are never
+/// reached (sprites, tables) come out as data rather than as nonsense instructions.
+pub fn disassemble(rom: &[u8]) -> String {
+    let mut memory = vec![0; PROGRAM_START];
+    memory.extend_from_slice(rom);
+    let end = memory.len();
+
+    let (code, labels) = trace(&memory);
+    let label = |address: usize| {
+        if labels.contains(&address) {
+            label_name(address)
+        } else {
+            format!("{:#05X}", address)
+        }
+    };
+
+    let mut listing = String::new();
+    let mut address = PROGRAM_START;
+
+    while address < end {
+        if labels.contains(&address) {
+            let _ = writeln!(listing, "{}:", label_name(address));
+        }
+
+        if code.contains(&address) {
+            let instruction = instruction::decode_at(&memory, address)
+                .expect("traced instructions lie within the rom");
+            let bytes: Vec<String> = memory[address..address + instruction.size()]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+
+            let _ = writeln!(listing, "{:04X}  {:<9} {}", address, bytes.join(""),
+                             instruction.mnemonic(label));
+            address += instruction.size();
+        }
+        else {
+            let byte = memory[address];
+            let _ = writeln!(listing, "{:04X}  {:02X}        DB {:#04X}  ; {}", address, byte,
+                             byte, sprite_row(byte));
+            address += 1;
+        }
+    }
+
+    listing
+}
+
+/// Walks every path from `PROGRAM_START`, returning the addresses that start an instruction
+/// and the targets of jumps and calls.
+fn trace(memory: &[u8]) -> (HashSet<usize>, BTreeSet<usize>) {
+    let mut code = HashSet::new();
+    let mut labels = BTreeSet::new();
+    let mut pending = vec![PROGRAM_START];
+
+    while let Some(address) = pending.pop() {
+        if address < PROGRAM_START || code.contains(&address) {
+            continue;
+        }
+
+        let instruction = match instruction::decode_at(memory, address) {
+            Some(Instruction::Unknown(_)) | None => continue,
+            Some(instruction) => instruction,
+        };
+        code.insert(address);
+
+        let next = address + instruction.size();
+        match instruction {
+            Instruction::Return | Instruction::Exit => {}
+            Instruction::Jump(nnn) => {
+                labels.insert(nnn);
+                pending.push(nnn);
+            }
+            // The offset register is only known at run time, so the table base is all we can follow.
+            Instruction::JumpOffset(_, nnn) => {
+                labels.insert(nnn);
+                pending.push(nnn);
+            }
+            Instruction::Call(nnn) => {
+                labels.insert(nnn);
+                pending.push(nnn);
+                pending.push(next);
+            }
+            Instruction::SkipEqualByte(..) |
+            Instruction::SkipNotEqualByte(..) |
+            Instruction::SkipEqual(..) |
+            Instruction::SkipNotEqual(..) |
+            Instruction::SkipKeyPressed(_) |
+            Instruction::SkipKeyNotPressed(_) => {
+                pending.push(next);
+                let skipped = instruction::decode_at(memory, next)
+                    .map_or(OPCODE_SIZE, |skipped| skipped.size());
+                pending.push(next + skipped);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    labels.retain(|&address| code.contains(&address));
+    (code, labels)
+}
+
+fn label_name(address: usize) -> String {
+    format!("L{:03X}", address)
+}
+
+fn sprite_row(byte: u8) -> String {
+    (0..8).map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The listing line for a single instruction at the start of the ROM.
+    fn first_line(rom: &[u8]) -> String {
+        disassemble(rom).lines().next().unwrap_or("").to_string()
+    }
+
+    #[test]
+    fn disassembles_every_opcode_family() {
+        let cases: &[(&[u8], &str)] = &[
+            (&[0x00, 0xE0], "0200  00E0      CLS"),
+            (&[0x00, 0xEE], "0200  00EE      RET"),
+            (&[0x00, 0xC3], "0200  00C3      SCD 3"),
+            (&[0x00, 0xD4], "0200  00D4      SCU 4"),
+            (&[0x00, 0xFB], "0200  00FB      SCR"),
+            (&[0x00, 0xFC], "0200  00FC      SCL"),
+            (&[0x00, 0xFD], "0200  00FD      EXIT"),
+            (&[0x00, 0xFE], "0200  00FE      LOW"),
+            (&[0x00, 0xFF], "0200  00FF      HIGH"),
+            (&[0x33, 0x7F], "0200  337F      SE V3, 0x7F"),
+            (&[0x4A, 0x01], "0200  4A01      SNE VA, 0x01"),
+            (&[0x51, 0x20], "0200  5120      SE V1, V2"),
+            (&[0x51, 0x32], "0200  5132      SAVE V1, V3"),
+            (&[0x51, 0x33], "0200  5133      LOAD V1, V3"),
+            (&[0x6C, 0xFF], "0200  6CFF      LD VC, 0xFF"),
+            (&[0x7E, 0x02], "0200  7E02      ADD VE, 0x02"),
+            (&[0x81, 0x20], "0200  8120      LD V1, V2"),
+            (&[0x81, 0x21], "0200  8121      OR V1, V2"),
+            (&[0x81, 0x22], "0200  8122      AND V1, V2"),
+            (&[0x81, 0x23], "0200  8123      XOR V1, V2"),
+            (&[0x81, 0x24], "0200  8124      ADD V1, V2"),
+            (&[0x81, 0x25], "0200  8125      SUB V1, V2"),
+            (&[0x81, 0x26], "0200  8126      SHR V1, V2"),
+            (&[0x81, 0x27], "0200  8127      SUBN V1, V2"),
+            (&[0x81, 0x2E], "0200  812E      SHL V1, V2"),
+            (&[0x9D, 0xE0], "0200  9DE0      SNE VD, VE"),
+            (&[0xA1, 0x23], "0200  A123      LD I, 0x123"),
+            (&[0xB2, 0x10], "0200  B210      JP V0, 0x210"),
+            (&[0xC5, 0x0F], "0200  C50F      RND V5, 0x0F"),
+            (&[0xD0, 0x15], "0200  D015      DRW V0, V1, 5"),
+            (&[0xE4, 0x9E], "0200  E49E      SKP V4"),
+            (&[0xE4, 0xA1], "0200  E4A1      SKNP V4"),
+            (&[0xF0, 0x00, 0x12, 0x34], "0200  F0001234  LD I, LONG 0x1234"),
+            (&[0xF2, 0x01], "0200  F201      PLANE 2"),
+            (&[0xF0, 0x02], "0200  F002      AUDIO"),
+            (&[0xF6, 0x07], "0200  F607      LD V6, DT"),
+            (&[0xF6, 0x0A], "0200  F60A      LD V6, K"),
+            (&[0xF6, 0x15], "0200  F615      LD DT, V6"),
+            (&[0xF6, 0x18], "0200  F618      LD ST, V6"),
+            (&[0xF6, 0x1E], "0200  F61E      ADD I, V6"),
+            (&[0xF6, 0x29], "0200  F629      LD F, V6"),
+            (&[0xF6, 0x30], "0200  F630      LD HF, V6"),
+            (&[0xF6, 0x33], "0200  F633      LD B, V6"),
+            (&[0xF6, 0x3A], "0200  F63A      PITCH V6"),
+            (&[0xF6, 0x55], "0200  F655      LD [I], V6"),
+            (&[0xF6, 0x65], "0200  F665      LD V6, [I]"),
+            (&[0xF6, 0x75], "0200  F675      LD R, V6"),
+            (&[0xF6, 0x85], "0200  F685      LD V6, R"),
+        ];
+
+        for &(rom, expected) in cases {
+            assert_eq!(first_line(rom), expected);
+        }
+    }
+
+    #[test]
+    fn unknown_opcodes_come_out_as_data() {
+        assert_eq!(first_line(&[0xFF, 0xFF]), "0200  FF        DB 0xFF  ; ########");
+    }
+
+    #[test]
+    fn jumps_and_calls_get_labels() {
+        let rom = [0x22, 0x06, 0x12, 0x04, 0x12, 0x04, 0x00, 0xEE];
+        let listing = disassemble(&rom);
+        let lines: Vec<&str> = listing.lines().map(str::trim_end).collect();
+
+        assert_eq!(lines, [
+            "0200  2206      CALL L206",
+            "0202  1204      JP L204",
+            "L204:",
+            "0204  1204      JP L204",
+            "L206:",
+            "0206  00EE      RET",
+        ]);
+    }
+
+    #[test]
+    fn skips_step_over_long_loads() {
+        let rom = [0x30, 0x00, 0xF0, 0x00, 0x02, 0x08, 0x00, 0xFD, 0x80];
+        let listing = disassemble(&rom);
+        let lines: Vec<&str> = listing.lines().map(str::trim_end).collect();
+
+        assert_eq!(lines, [
+            "0200  3000      SE V0, 0x00",
+            "0202  F0000208  LD I, LONG 0x208",
+            "0206  00FD      EXIT",
+            "0208  80        DB 0x80  ; #.......",
+        ]);
+    }
+}
@@ -1,62 +1,163 @@
 use sdl2;
-use sdl2::pixels;
+use sdl2::hint;
+use sdl2::pixels::{self, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+use config::Config;
+use filter::Filter;
+use frontend::VideoSink;
+use palette::{self, Palette};
+use viewport::Viewport;
 
 use CHIP8_WIDTH;
 use CHIP8_HEIGHT;
+use Framebuffer;
 
-const SCREEN_SCALE: u32 = 20;
-const SCREEN_WIDTH: u32 = (CHIP8_WIDTH as u32) * SCREEN_SCALE;
-const SCREEN_HEIGHT: u32 = (CHIP8_HEIGHT as u32) * SCREEN_SCALE;
+const LETTERBOX: pixels::Color = pixels::Color { r: 0, g: 0, b: 0, a: 0xff };
 
 const PROGRAM_TITLE: &str = "Chip 8 Emulator";
+const WAITING_TITLE: &str = "Chip 8 Emulator - press a key";
 
-pub struct Display {
+pub struct Display<'a> {
     canvas: Canvas<Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    waiting_for_key: bool,
+    /// The configured palette followed by the other named ones, cycled through by a hotkey.
+    palettes: Vec<Palette>,
+    palette: usize,
+    integer_scale: bool,
+    vsync: bool,
+    /// The latest frame from the CPU, filtered again on every present.
+    frame: Framebuffer,
+    filter: Filter,
+    /// Holds the filtered frame at its native resolution, one RGB pixel per CHIP-8 pixel.
+    texture: Texture<'a>,
+    texture_size: (usize, usize),
+    rgb: Vec<u8>,
 }
 
-impl Display {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
-        let video_subsystem = sdl_context.video().unwrap();
-        let window = video_subsystem.window(PROGRAM_TITLE,
-                                                         SCREEN_WIDTH,
-                                                        SCREEN_HEIGHT)
-            .position_centered()
-            .build()
-            .unwrap();
+/// Opens the window. The canvas's texture creator has to outlive the `Display` made from it.
+pub fn create_canvas(sdl_context: &sdl2::Sdl, config: &Config) -> Canvas<Window> {
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem.window(PROGRAM_TITLE,
+                                                     CHIP8_WIDTH as u32 * config.scale,
+                                                    CHIP8_HEIGHT as u32 * config.scale)
+        .position_centered()
+        .resizable()
+        .allow_highdpi()
+        .build()
+        .unwrap();
+
+    // Scale the texture up with hard pixel edges rather than blurring it.
+    hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+
+    let canvas = window.into_canvas();
+    let canvas = if config.vsync { canvas.present_vsync() } else { canvas };
+    canvas.build().unwrap()
+}
 
-        let mut canvas = window.into_canvas().build().unwrap();
+impl<'a> Display<'a> {
+    pub fn new(mut canvas: Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>,
+               config: &Config) -> Self {
+        if config.fullscreen {
+            let _ = canvas.window_mut().set_fullscreen(FullscreenType::Desktop);
+        }
 
-        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        let palette = config.palette;
+        canvas.set_draw_color(LETTERBOX);
         canvas.clear();
         canvas.present();
 
-        Display { canvas }
+        let mut palettes = vec![palette];
+        palettes.extend(palette::NAMES.iter()
+            .filter_map(|name| Palette::from_name(name))
+            .filter(|&named| named != palette));
+
+        Display {
+            waiting_for_key: false,
+            palettes,
+            palette: 0,
+            integer_scale: config.integer_scale,
+            vsync: config.vsync,
+            frame: Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT),
+            filter: Filter::new(config.filter, config.decay, config.blend_frames),
+            texture: create_texture(texture_creator, CHIP8_WIDTH, CHIP8_HEIGHT),
+            texture_size: (CHIP8_WIDTH, CHIP8_HEIGHT),
+            rgb: Vec::new(),
+            canvas,
+            texture_creator,
+        }
+    }
+
+    /// The renderer's size in real pixels, which differs from the window size on high-DPI
+    /// displays.
+    fn output_size(&self) -> (u32, u32) {
+        self.canvas.output_size().unwrap_or_else(|_| self.canvas.window().drawable_size())
     }
+}
 
-    pub fn draw(&mut self, pixels: &[[u8; CHIP8_WIDTH]; CHIP8_HEIGHT]) {
-        for y in 0..CHIP8_HEIGHT {
-            for x in 0..CHIP8_WIDTH {
+fn create_texture(texture_creator: &TextureCreator<WindowContext>, width: usize, height: usize)
+    -> Texture<'_> {
+    texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+        .unwrap()
+}
 
-                self.canvas.set_draw_color(colour(pixels[y][x]));
+impl<'a> VideoSink for Display<'a> {
+    /// Keeps the frame until the next `present`.
+    fn draw(&mut self, pixels: &Framebuffer) {
+        self.frame.clone_from(pixels);
+    }
 
-                let x = (x as u32) * SCREEN_SCALE;
-                let y = (y as u32) * SCREEN_SCALE;
-                self.canvas.fill_rect(Rect::new(x as i32,
-                                                     y as i32,
-                                                SCREEN_SCALE,
-                                                SCREEN_SCALE));
-            }
+    /// Filters the frame into the texture and letterboxes it into the window, waiting for the
+    /// vertical blank when vsync is on.
+    ///
+    /// This runs every host frame so that the filter can fade pixels out while the frame is
+    /// unchanged.
+    fn present(&mut self) {
+        let size = (self.frame.width(), self.frame.height());
+        if size != self.texture_size {
+            self.texture = create_texture(self.texture_creator, size.0, size.1);
+            self.texture_size = size;
         }
+
+        self.filter.apply(&self.frame, &self.palettes[self.palette], &mut self.rgb);
+        let _ = self.texture.update(None, &self.rgb, size.0 * 3);
+
+        let viewport = Viewport::letterbox(self.output_size(), self.texture_size, self.integer_scale);
+
+        self.canvas.set_draw_color(LETTERBOX);
+        self.canvas.clear();
+        let _ = self.canvas.copy(&self.texture, None, Rect::new(viewport.x as i32,
+                                                                     viewport.y as i32,
+                                                                viewport.width,
+                                                                viewport.height));
         self.canvas.present();
     }
-}
 
-fn colour(pixel: u8) -> pixels::Color {
-    match pixel {
-        0 => pixels::Color::RGB(0, 0, 0),
-        _ => pixels::Color::RGB(255, 255, 255),
+    fn paces_frames(&self) -> bool {
+        self.vsync
+    }
+
+    fn set_waiting_for_key(&mut self, waiting: bool) {
+        if waiting != self.waiting_for_key {
+            self.waiting_for_key = waiting;
+            let title = if waiting { WAITING_TITLE } else { PROGRAM_TITLE };
+            let _ = self.canvas.window_mut().set_title(title);
+        }
+    }
+
+    fn next_palette(&mut self) {
+        self.palette = (self.palette + 1) % self.palettes.len();
     }
-}
\ No newline at end of file
+
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        let _ = window.set_fullscreen(fullscreen);
+    }
+}
@@ -0,0 +1,41 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmulatorError {
+    /// `2NNN` called with all 16 stack entries in use.
+    StackOverflow { address: usize },
+    /// `00EE` returned with an empty stack.
+    StackUnderflow { address: usize },
+    /// An instruction fetch or memory access fell outside of RAM.
+    MemoryOutOfRange { address: usize },
+    UnknownOpcode { address: usize, opcode: u16 },
+    RomTooLarge { size: usize, max: usize },
+    InvalidSaveState(String),
+    InvalidMovie(String),
+    Io(String),
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmulatorError::StackOverflow { address } =>
+                write!(f, "stack overflow at {:#05x}", address),
+            EmulatorError::StackUnderflow { address } =>
+                write!(f, "stack underflow at {:#05x}", address),
+            EmulatorError::MemoryOutOfRange { address } =>
+                write!(f, "memory access out of range at {:#06x}", address),
+            EmulatorError::UnknownOpcode { address, opcode } =>
+                write!(f, "unknown opcode {:04X} at {:#05x}", opcode, address),
+            EmulatorError::RomTooLarge { size, max } =>
+                write!(f, "rom is {} bytes, the most that fits in memory is {}", size, max),
+            EmulatorError::InvalidSaveState(ref reason) =>
+                write!(f, "invalid save state: {}", reason),
+            EmulatorError::InvalidMovie(ref reason) =>
+                write!(f, "invalid movie: {}", reason),
+            EmulatorError::Io(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for EmulatorError {}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use palette::Palette;
+use Framebuffer;
+
+/// Share of a pixel's glow kept from one host frame to the next.
+pub const DEFAULT_DECAY: f32 = 0.5;
+
+/// Frames combined by the `Or` filter.
+pub const DEFAULT_BLEND_FRAMES: usize = 2;
+
+/// Ways of hiding the flicker of sprites that are erased and redrawn every frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterMode {
+    Off,
+    /// Lit pixels light up at once and fade out over several frames, like a CRT's phosphor.
+    Phosphor,
+    /// A pixel is lit if it was lit in any of the last few frames.
+    Or,
+}
+
+impl FilterMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(FilterMode::Off),
+            "phosphor" => Some(FilterMode::Phosphor),
+            "or" => Some(FilterMode::Or),
+            _ => None,
+        }
+    }
+}
+
+/// Turns the framebuffer into the colours shown, once per host frame.
+pub struct Filter {
+    mode: FilterMode,
+    decay: f32,
+    blend_frames: usize,
+    history: VecDeque<Framebuffer>,
+    /// The colour shown for every pixel last frame, as RGB triples.
+    glow: Vec<f32>,
+}
+
+impl Filter {
+    pub fn new(mode: FilterMode, decay: f32, blend_frames: usize) -> Self {
+        Filter {
+            mode,
+            decay,
+            blend_frames: blend_frames.max(1),
+            history: VecDeque::new(),
+            glow: Vec::new(),
+        }
+    }
+
+    /// Writes one RGB triple per pixel into `rgb`, replacing its contents.
+    pub fn apply(&mut self, frame: &Framebuffer, palette: &Palette, rgb: &mut Vec<u8>) {
+        rgb.clear();
+
+        match self.mode {
+            FilterMode::Off => {
+                for &pixel in frame.pixels() {
+                    let (r, g, b) = palette.colour(pixel);
+                    rgb.extend_from_slice(&[r, g, b]);
+                }
+            }
+            FilterMode::Or => {
+                let size = (frame.width(), frame.height());
+                let resized = self.history.back().is_some_and(|last| (last.width(), last.height()) != size);
+                if resized {
+                    self.history.clear();
+                }
+                if self.history.len() == self.blend_frames {
+                    self.history.pop_front();
+                }
+                self.history.push_back(frame.clone());
+
+                for i in 0..frame.pixels().len() {
+                    let pixel = self.history.iter().fold(0, |pixel, old| pixel | old.pixels()[i]);
+                    let (r, g, b) = palette.colour(pixel);
+                    rgb.extend_from_slice(&[r, g, b]);
+                }
+            }
+            FilterMode::Phosphor => {
+                let fresh = self.glow.len() != frame.pixels().len() * 3;
+                if fresh {
+                    self.glow = vec![0.0; frame.pixels().len() * 3];
+                }
+
+                for (&pixel, glow) in frame.pixels().iter().zip(self.glow.chunks_mut(3)) {
+                    let (r, g, b) = palette.colour(pixel);
+                    for (channel, target) in glow.iter_mut().zip(&[r, g, b]) {
+                        let target = *target as f32;
+                        *channel = if pixel != 0 || fresh {
+                            target
+                        } else {
+                            target + (*channel - target) * self.decay
+                        };
+                        rgb.push(channel.round() as u8);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(lit: bool) -> Framebuffer {
+        let mut frame = Framebuffer::new(2, 1);
+        frame.set(0, 0, lit as u8);
+        frame
+    }
+
+    fn first_pixel(filter: &mut Filter, lit: bool) -> (u8, u8, u8) {
+        let mut rgb = Vec::new();
+        filter.apply(&frame(lit), &Palette::classic(), &mut rgb);
+        assert_eq!(rgb.len(), 6);
+        (rgb[0], rgb[1], rgb[2])
+    }
+
+    #[test]
+    fn off_shows_the_frame_as_it_is() {
+        let mut filter = Filter::new(FilterMode::Off, DEFAULT_DECAY, DEFAULT_BLEND_FRAMES);
+        assert_eq!(first_pixel(&mut filter, true), (0xff, 0xff, 0xff));
+        assert_eq!(first_pixel(&mut filter, false), (0, 0, 0));
+    }
+
+    #[test]
+    fn or_keeps_pixels_lit_for_the_last_frames() {
+        let mut filter = Filter::new(FilterMode::Or, DEFAULT_DECAY, 3);
+        assert_eq!(first_pixel(&mut filter, true), (0xff, 0xff, 0xff));
+        assert_eq!(first_pixel(&mut filter, false), (0xff, 0xff, 0xff));
+        assert_eq!(first_pixel(&mut filter, false), (0xff, 0xff, 0xff));
+        assert_eq!(first_pixel(&mut filter, false), (0, 0, 0));
+    }
+
+    #[test]
+    fn phosphor_lights_at_once_and_fades_out() {
+        let mut filter = Filter::new(FilterMode::Phosphor, 0.5, DEFAULT_BLEND_FRAMES);
+        assert_eq!(first_pixel(&mut filter, false), (0, 0, 0));
+        assert_eq!(first_pixel(&mut filter, true), (0xff, 0xff, 0xff));
+        assert_eq!(first_pixel(&mut filter, false), (0x80, 0x80, 0x80));
+        assert_eq!(first_pixel(&mut filter, false), (0x40, 0x40, 0x40));
+        assert_eq!(first_pixel(&mut filter, true), (0xff, 0xff, 0xff));
+    }
+}
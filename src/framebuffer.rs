@@ -0,0 +1,106 @@
+/// The display, stored row by row with one byte per pixel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// Rebuilds a framebuffer from `pixels()`, or `None` if the sizes disagree.
+    pub fn from_pixels(width: usize, height: usize, pixels: &[u8]) -> Option<Self> {
+        if pixels.len() != width * height {
+            return None;
+        }
+
+        Some(Framebuffer { width, height, pixels: pixels.to_vec() })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn rows(&self) -> ::std::slice::Chunks<'_, u8> {
+        self.pixels.chunks(self.width)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    pub fn clear(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = 0;
+        }
+    }
+
+    /// Clears only the given bit planes, leaving the others intact.
+    pub fn clear_planes(&mut self, planes: u8) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !planes;
+        }
+    }
+
+    /// Switches to a new resolution, clearing the screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Framebuffer::new(width, height);
+    }
+
+    pub fn scroll_up(&mut self, rows: usize, planes: u8) {
+        self.scroll(0, -(rows as isize), planes);
+    }
+
+    pub fn scroll_down(&mut self, rows: usize, planes: u8) {
+        self.scroll(0, rows as isize, planes);
+    }
+
+    pub fn scroll_left(&mut self, columns: usize, planes: u8) {
+        self.scroll(-(columns as isize), 0, planes);
+    }
+
+    pub fn scroll_right(&mut self, columns: usize, planes: u8) {
+        self.scroll(columns as isize, 0, planes);
+    }
+
+    /// Moves the given bit planes by (dx, dy), filling the vacated area with zeroes.
+    fn scroll(&mut self, dx: isize, dy: isize, planes: u8) {
+        let source = self.pixels.clone();
+        let width = self.width as isize;
+        let height = self.height as isize;
+
+        for y in 0..height {
+            for x in 0..width {
+                let from_x = x - dx;
+                let from_y = y - dy;
+                let moved = if from_x >= 0 && from_x < width && from_y >= 0 && from_y < height {
+                    source[(from_y * width + from_x) as usize] & planes
+                } else {
+                    0
+                };
+
+                let index = (y * width + x) as usize;
+                self.pixels[index] = (source[index] & !planes) | moved;
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cpu::{AudioPattern, Output, CPU};
+use error::EmulatorError;
+use rewind::Rewind;
+use savestate::SaveSlots;
+use Framebuffer;
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// How far the host loop may fall behind before it gives up catching up.
+const MAX_FRAME_LAG: u32 = 5;
+
+pub trait VideoSink {
+    /// Takes a frame whose pixels changed.
+    fn draw(&mut self, pixels: &Framebuffer);
+
+    /// Shows the latest frame; called once per host frame, whether or not it changed.
+    fn present(&mut self) {}
+
+    /// Told every frame whether the program is blocked in `FX0A`.
+    fn set_waiting_for_key(&mut self, _waiting: bool) {}
+
+    /// Switches to the next colour palette; the frame is drawn again afterwards.
+    fn next_palette(&mut self) {}
+
+    fn toggle_fullscreen(&mut self) {}
+
+    /// Whether `present` already waits for the display's refresh, in which case the frontend
+    /// does not pace frames itself.
+    fn paces_frames(&self) -> bool {
+        false
+    }
+}
+
+pub trait AudioSink {
+    fn start_beep(&mut self);
+    fn stop_beep(&mut self);
+
+    /// Replaces the plain beep with an XO-CHIP audio pattern.
+    fn set_pattern(&mut self, _pattern: &AudioPattern) {}
+}
+
+/// Emulator controls that sit outside the CHIP-8 keypad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    SelectSlot(usize),
+    /// Reported on every poll while held, stepping back one frame each time.
+    Rewind,
+    NextPalette,
+    ToggleFullscreen,
+}
+
+pub trait KeypadSource {
+    /// Returns the state of the 16 keys, or `None` once the user has asked to quit.
+    fn poll(&mut self) -> Option<[bool; 16]>;
+
+    /// Hotkeys pressed during the last poll.
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        Vec::new()
+    }
+
+    /// Told when the frame just polled was not run because of `Hotkey::Rewind`, and whether
+    /// an earlier frame was stepped back over.
+    fn rewound(&mut self, _stepped_back: bool) {}
+}
+
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn draw(&mut self, _pixels: &Framebuffer) {}
+}
+
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn start_beep(&mut self) {}
+    fn stop_beep(&mut self) {}
+}
+
+pub struct NullKeypad;
+
+impl KeypadSource for NullKeypad {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        Some([false; 16])
+    }
+}
+
+pub fn run<V, A, K>(cpu: &mut CPU, video: &mut V, audio: &mut A, keypad: &mut K,
+                    slots: &mut SaveSlots, rewind: &mut Rewind) -> Result<(), EmulatorError>
+    where V: VideoSink, A: AudioSink, K: KeypadSource {
+
+    let mut clock = FrameClock::new(video.paces_frames());
+    let mut audio_pattern = None;
+
+    rewind.record(cpu);
+
+    while let Some(keys) = keypad.poll() {
+
+        let hotkeys = keypad.hotkeys();
+        for &hotkey in &hotkeys {
+            handle_hotkey(hotkey, cpu, slots, rewind);
+        }
+
+        if hotkeys.contains(&Hotkey::NextPalette) {
+            video.next_palette();
+            video.draw(cpu.video_memory());
+        }
+        if hotkeys.contains(&Hotkey::ToggleFullscreen) {
+            video.toggle_fullscreen();
+        }
+
+        if hotkeys.contains(&Hotkey::Rewind) {
+            let stepped_back = rewind.step_back(cpu)?;
+            if stepped_back {
+                present(&cpu.output(), video, audio, &mut audio_pattern);
+            }
+            keypad.rewound(stepped_back);
+            clock.wait();
+            continue;
+        }
+
+        let exited = {
+            let output = cpu.cpu_cycle(keys)?;
+            present(&output, video, audio, &mut audio_pattern);
+            output.exited
+        };
+
+        if exited {
+            break;
+        }
+
+        rewind.record(cpu);
+        clock.wait();
+    }
+
+    Ok(())
+}
+
+/// Save and load failures are reported but never stop the running program.
+///
+/// Loading a state starts the rewind history afresh, since the frames before it led
+/// somewhere else.
+fn handle_hotkey(hotkey: Hotkey, cpu: &mut CPU, slots: &mut SaveSlots, rewind: &mut Rewind) {
+    let result = match hotkey {
+        Hotkey::SaveState => slots.save(cpu),
+        Hotkey::LoadState => slots.load(cpu).map(|()| {
+            rewind.clear();
+            rewind.record(cpu);
+        }),
+        Hotkey::SelectSlot(slot) => {
+            slots.select(slot);
+            eprintln!("Save slot {} selected", slots.slot());
+            return;
+        }
+        Hotkey::Rewind | Hotkey::NextPalette | Hotkey::ToggleFullscreen => return,
+    };
+
+    if let Err(err) = result {
+        eprintln!("Warning: save slot {}: {}", slots.slot(), err);
+    }
+}
+
+/// Hands a finished frame to the video and audio sinks.
+pub(crate) fn present<V, A>(output: &Output, video: &mut V, audio: &mut A,
+                            audio_pattern: &mut Option<AudioPattern>)
+    where V: VideoSink, A: AudioSink {
+
+    if output.video_memory_changed {
+        video.draw(output.video_memory);
+    }
+    video.set_waiting_for_key(output.waiting_for_key);
+    video.present();
+
+    if output.audio_pattern != *audio_pattern {
+        *audio_pattern = output.audio_pattern;
+        if let Some(ref pattern) = *audio_pattern {
+            audio.set_pattern(pattern);
+        }
+    }
+
+    if output.beep {
+        audio.start_beep();
+    }
+    else {
+        audio.stop_beep();
+    }
+}
+
+/// Paces the host loop at 60 frames per second against the wall clock, unless the video sink
+/// is already paced by the display.
+pub(crate) struct FrameClock {
+    next_frame: Instant,
+    paced_by_video: bool,
+}
+
+impl FrameClock {
+    pub(crate) fn new(paced_by_video: bool) -> Self {
+        FrameClock { next_frame: Instant::now(), paced_by_video }
+    }
+
+    /// Sleeps until the next frame is due, or resynchronises after falling too far behind.
+    pub(crate) fn wait(&mut self) {
+        if self.paced_by_video {
+            return;
+        }
+
+        self.next_frame += FRAME_DURATION;
+        let now = Instant::now();
+
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        }
+        else if now - self.next_frame > FRAME_DURATION * MAX_FRAME_LAG {
+            self.next_frame = now;
+        }
+    }
+}
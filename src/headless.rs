@@ -0,0 +1,162 @@
+use std::fs;
+
+use cpu::CPU;
+use debugger;
+use error::EmulatorError;
+use frontend::KeypadSource;
+use Framebuffer;
+
+/// How long a headless run lasts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Frames(usize),
+    Cycles(usize),
+}
+
+/// What a headless run got through before it stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub frames: usize,
+    pub cycles: usize,
+    pub exited: bool,
+}
+
+/// Runs the CPU as fast as possible with no window or audio.
+///
+/// The run ends at the limit, when the program exits, or when the keypad runs out of input.
+pub fn run<K: KeypadSource>(cpu: &mut CPU, keypad: &mut K, limit: Limit)
+    -> Result<Summary, EmulatorError> {
+
+    let mut summary = Summary { frames: 0, cycles: 0, exited: false };
+
+    loop {
+        if let Limit::Frames(frames) = limit {
+            if summary.frames >= frames {
+                break;
+            }
+        }
+
+        let keys = match keypad.poll() {
+            Some(keys) => keys,
+            None => break,
+        };
+        cpu.begin_frame(keys);
+        summary.frames += 1;
+
+        while !cpu.frame_finished() {
+            if let Limit::Cycles(cycles) = limit {
+                if summary.cycles >= cycles {
+                    return Ok(summary);
+                }
+            }
+
+            cpu.step_instruction()?;
+            summary.cycles += 1;
+        }
+
+        if cpu.output().exited {
+            summary.exited = true;
+            break;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Keys held from a given frame until the next entry, read from a text file such as:
+///
+/// ```text
+/// # frame  keys (hex, or - for none)
+/// 0        -
+/// 30       5
+/// 34       1 2
+/// ```
+pub struct Script {
+    entries: Vec<(usize, [bool; 16])>,
+    frame: usize,
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Script::parse(&text).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries: Vec<(usize, [bool; 16])> = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let frame = match words.next() {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            let frame: usize = frame.parse()
+                .map_err(|_| format!("line {}: '{}' is not a frame number", number + 1, frame))?;
+            if entries.last().is_some_and(|&(last, _)| frame < last) {
+                return Err(format!("line {}: frames must be in order", number + 1));
+            }
+
+            let mut keys = [false; 16];
+            for word in words.filter(|&word| word != "-") {
+                match usize::from_str_radix(word, 16) {
+                    Ok(key) if key < 16 => keys[key] = true,
+                    _ => return Err(format!("line {}: '{}' is not a key", number + 1, word)),
+                }
+            }
+            entries.push((frame, keys));
+        }
+
+        Ok(Script { entries, frame: 0 })
+    }
+}
+
+impl KeypadSource for Script {
+    /// Never runs out: the last entry is held for the rest of the run.
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        let keys = self.entries.iter()
+            .take_while(|&&(frame, _)| frame <= self.frame)
+            .last()
+            .map_or([false; 16], |&(_, keys)| keys);
+
+        self.frame += 1;
+        Some(keys)
+    }
+}
+
+/// A plain (P1) PBM image with lit pixels, on any plane, in black.
+pub fn pbm(pixels: &Framebuffer) -> String {
+    let mut image = format!("P1\n{} {}\n", pixels.width(), pixels.height());
+
+    for row in pixels.rows() {
+        let bits: Vec<&str> = row.iter().map(|&pixel| if pixel != 0 { "1" } else { "0" }).collect();
+        image.push_str(&bits.join(" "));
+        image.push('\n');
+    }
+
+    image
+}
+
+/// A 64-bit FNV-1a hash of the framebuffer's size and pixels, for cheap golden comparisons.
+pub fn video_hash(pixels: &Framebuffer) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    let size = [pixels.width() as u16, pixels.height() as u16];
+    let header = size.iter().flat_map(|dimension| dimension.to_le_bytes().to_vec());
+
+    for byte in header.chain(pixels.pixels().iter().cloned()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// The summary, registers and video hash printed at the end of a run.
+pub fn report(cpu: &CPU, summary: &Summary) -> String {
+    format!("frames {}  cycles {}{}\n{}video hash {:#018x}\n",
+            summary.frames, summary.cycles, if summary.exited { "  (exited)" } else { "" },
+            debugger::registers_report(cpu), video_hash(cpu.video_memory()))
+}
@@ -1,22 +1,108 @@
 use sdl2;
+use sdl2::controller::{Button, GameController};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
+use config::{self, Config, KeyMap};
+use frontend::{Hotkey, KeypadSource};
+
 pub struct Input {
     events: sdl2::EventPump,
+    controller_subsystem: sdl2::GameControllerSubsystem,
+    hotkeys: Vec<Hotkey>,
+    key_map: Vec<(Keycode, usize)>,
+    /// Controllers in the order they were plugged in, one per player.
+    controllers: [Option<GameController>; config::CONTROLLER_COUNT],
+    button_maps: [Vec<(Button, usize)>; config::CONTROLLER_COUNT],
 }
 
 impl Input {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
-        Input { events: sdl_context.event_pump().unwrap() }
+    /// Fails if the config names a key or button that SDL does not know.
+    pub fn new(sdl_context: &sdl2::Sdl, config: &Config) -> Result<Self, String> {
+        let key_map = parse_map(&config.keyboard, Keycode::from_name)?;
+        let button_maps = [
+            parse_map(&config.controllers[0], Button::from_string)?,
+            parse_map(&config.controllers[1], Button::from_string)?,
+        ];
+
+        // Controllers already connected are reported as added on the first poll.
+        Ok(Input {
+            events: sdl_context.event_pump().unwrap(),
+            controller_subsystem: sdl_context.game_controller().unwrap(),
+            hotkeys: Vec::new(),
+            key_map,
+            controllers: [None, None],
+            button_maps,
+        })
+    }
+
+    fn add_controller(&mut self, joystick_index: u32) {
+        let controller = match self.controller_subsystem.open(joystick_index) {
+            Ok(controller) => controller,
+            Err(err) => {
+                eprintln!("Warning: could not open game controller {}: {}", joystick_index, err);
+                return;
+            }
+        };
+
+        let id = controller.instance_id();
+        if self.controllers.iter().flatten().any(|open| open.instance_id() == id) {
+            return;
+        }
+        if let Some(slot) = self.controllers.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(controller);
+        }
+    }
+
+    fn remove_controller(&mut self, instance_id: i32) {
+        for slot in &mut self.controllers {
+            if slot.as_ref().is_some_and(|controller| controller.instance_id() == instance_id) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+fn parse_map<T, F>(map: &KeyMap, parse: F) -> Result<Vec<(T, usize)>, String>
+    where F: Fn(&str) -> Option<T> {
+
+    let mut parsed = Vec::new();
+    for (key, names) in map.iter().enumerate() {
+        for name in names {
+            let host_key = parse(name)
+                .ok_or_else(|| format!("unknown key or button '{}' for keypad key {:X}", name, key))?;
+            parsed.push((host_key, key));
+        }
     }
+    Ok(parsed)
+}
 
-    pub fn poll(&mut self) -> Result<[bool; 16], ()> {
+impl KeypadSource for Input {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        self.hotkeys.clear();
 
-        for event in self.events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err(());
-            };
+        let events: Vec<Event> = self.events.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit { .. } => return None,
+                Event::KeyDown { keycode: Some(key), repeat: false, .. } => {
+                    let hotkey = match key {
+                        Keycode::F1 => Some(Hotkey::SelectSlot(1)),
+                        Keycode::F2 => Some(Hotkey::SelectSlot(2)),
+                        Keycode::F3 => Some(Hotkey::SelectSlot(3)),
+                        Keycode::F4 => Some(Hotkey::SelectSlot(4)),
+                        Keycode::F5 => Some(Hotkey::SaveState),
+                        Keycode::F7 => Some(Hotkey::NextPalette),
+                        Keycode::F11 => Some(Hotkey::ToggleFullscreen),
+                        Keycode::F9 => Some(Hotkey::LoadState),
+                        _ => None,
+                    };
+                    self.hotkeys.extend(hotkey);
+                }
+                Event::ControllerDeviceAdded { which, .. } => self.add_controller(which),
+                Event::ControllerDeviceRemoved { which, .. } => self.remove_controller(which),
+                _ => {}
+            }
         }
 
         let keys: Vec<Keycode> = self.events
@@ -25,30 +111,32 @@ impl Input {
             .filter_map(Keycode::from_scancode)
             .collect();
 
+        if keys.contains(&Keycode::Backspace) {
+            self.hotkeys.push(Hotkey::Rewind);
+        }
+
         let mut chip8_keys = [false; 16];
 
-        for key in keys {
-            match key {
-                Keycode::Num1 => Some(chip8_keys[0x1] = true),
-                Keycode::Num2 => Some(chip8_keys[0x2] = true),
-                Keycode::Num3 => Some(chip8_keys[0x3] = true),
-                Keycode::Num4 => Some(chip8_keys[0xc] = true),
-                Keycode::Q => Some(chip8_keys[0x4] = true),
-                Keycode::W => Some(chip8_keys[0x5] = true),
-                Keycode::E => Some(chip8_keys[0x6] = true),
-                Keycode::R => Some(chip8_keys[0xd] = true),
-                Keycode::A => Some(chip8_keys[0x7] = true),
-                Keycode::S => Some(chip8_keys[0x8] = true),
-                Keycode::D => Some(chip8_keys[0x9] = true),
-                Keycode::F => Some(chip8_keys[0xe] = true),
-                Keycode::Z => Some(chip8_keys[0xa] = true),
-                Keycode::X => Some(chip8_keys[0x0] = true),
-                Keycode::C => Some(chip8_keys[0xb] = true),
-                Keycode::V => Some(chip8_keys[0xf] = true),
-                _ => None,
-            };
-        }
-
-        Ok(chip8_keys)
+        for &(keycode, key) in &self.key_map {
+            if keys.contains(&keycode) {
+                chip8_keys[key] = true;
+            }
+        }
+
+        for (controller, buttons) in self.controllers.iter().zip(self.button_maps.iter()) {
+            if let Some(controller) = controller {
+                for &(button, key) in buttons {
+                    if controller.button(button) {
+                        chip8_keys[key] = true;
+                    }
+                }
+            }
+        }
+
+        Some(chip8_keys)
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        self.hotkeys.drain(..).collect()
     }
 }
\ No newline at end of file
@@ -0,0 +1,308 @@
+use std::fmt;
+
+pub const OPCODE_SIZE: usize = 2;
+
+/// `F000 NNNN` is the only instruction followed by an operand word.
+const LONG_LOAD_OPCODE: u16 = 0xF000;
+
+/// Machine cycles the VIP interpreter spends fetching and decoding every instruction.
+const VIP_FETCH_CYCLES: usize = 40;
+
+/// Extra machine cycles a VIP skip instruction takes when it skips.
+const VIP_SKIP_CYCLES: usize = 4;
+
+/// A decoded instruction, shared by the CPU and the disassembler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    ScrollDown(usize),
+    ScrollUp(usize),
+    Clear,
+    Return,
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
+    Jump(usize),
+    Call(usize),
+    SkipEqualByte(usize, u8),
+    SkipNotEqualByte(usize, u8),
+    SkipEqual(usize, usize),
+    SaveRange(usize, usize),
+    LoadRange(usize, usize),
+    LoadByte(usize, u8),
+    AddByte(usize, u8),
+    Move(usize, usize),
+    Or(usize, usize),
+    And(usize, usize),
+    Xor(usize, usize),
+    Add(usize, usize),
+    Sub(usize, usize),
+    ShiftRight(usize, usize),
+    SubReverse(usize, usize),
+    ShiftLeft(usize, usize),
+    SkipNotEqual(usize, usize),
+    LoadI(usize),
+    JumpOffset(usize, usize),
+    Random(usize, u8),
+    Draw(usize, usize, usize),
+    SkipKeyPressed(usize),
+    SkipKeyNotPressed(usize),
+    LoadLongI(usize),
+    SelectPlanes(usize),
+    LoadAudio,
+    GetDelay(usize),
+    WaitKey(usize),
+    SetDelay(usize),
+    SetSound(usize),
+    AddI(usize),
+    Font(usize),
+    BigFont(usize),
+    Bcd(usize),
+    SetPitch(usize),
+    Store(usize),
+    Load(usize),
+    SaveFlags(usize),
+    LoadFlags(usize),
+    Unknown(u16),
+}
+
+/// Whether `opcode` is followed by an operand word, making the instruction four bytes long.
+pub fn is_long(opcode: u16) -> bool {
+    opcode == LONG_LOAD_OPCODE
+}
+
+/// Decodes `opcode`; `operand` is the following word and only matters when `is_long(opcode)`.
+pub fn decode(opcode: u16, operand: u16) -> Instruction {
+    let nibbles = (
+        (opcode & 0xF000) >> 12,
+        (opcode & 0x0F00) >> 8,
+        (opcode & 0x00F0) >> 4,
+        (opcode & 0x000F) as u8,
+    );
+
+    let nnn = (opcode & 0x0FFF) as usize;
+    let kk = (opcode & 0x00FF) as u8;
+    let x = nibbles.1 as usize;
+    let y = nibbles.2 as usize;
+    let n = nibbles.3 as usize;
+
+    match nibbles {
+        (0x00, 0x00, 0x0c, _) => Instruction::ScrollDown(n),
+        (0x00, 0x00, 0x0d, _) => Instruction::ScrollUp(n),
+        (0x00, 0x00, 0x0e, 0x00) => Instruction::Clear,
+        (0x00, 0x00, 0x0e, 0x0e) => Instruction::Return,
+        (0x00, 0x00, 0x0f, 0x0b) => Instruction::ScrollRight,
+        (0x00, 0x00, 0x0f, 0x0c) => Instruction::ScrollLeft,
+        (0x00, 0x00, 0x0f, 0x0d) => Instruction::Exit,
+        (0x00, 0x00, 0x0f, 0x0e) => Instruction::LowRes,
+        (0x00, 0x00, 0x0f, 0x0f) => Instruction::HighRes,
+        (0x01, _, _, _) => Instruction::Jump(nnn),
+        (0x02, _, _, _) => Instruction::Call(nnn),
+        (0x03, _, _, _) => Instruction::SkipEqualByte(x, kk),
+        (0x04, _, _, _) => Instruction::SkipNotEqualByte(x, kk),
+        (0x05, _, _, 0x00) => Instruction::SkipEqual(x, y),
+        (0x05, _, _, 0x02) => Instruction::SaveRange(x, y),
+        (0x05, _, _, 0x03) => Instruction::LoadRange(x, y),
+        (0x06, _, _, _) => Instruction::LoadByte(x, kk),
+        (0x07, _, _, _) => Instruction::AddByte(x, kk),
+        (0x08, _, _, 0x00) => Instruction::Move(x, y),
+        (0x08, _, _, 0x01) => Instruction::Or(x, y),
+        (0x08, _, _, 0x02) => Instruction::And(x, y),
+        (0x08, _, _, 0x03) => Instruction::Xor(x, y),
+        (0x08, _, _, 0x04) => Instruction::Add(x, y),
+        (0x08, _, _, 0x05) => Instruction::Sub(x, y),
+        (0x08, _, _, 0x06) => Instruction::ShiftRight(x, y),
+        (0x08, _, _, 0x07) => Instruction::SubReverse(x, y),
+        (0x08, _, _, 0x0e) => Instruction::ShiftLeft(x, y),
+        (0x09, _, _, 0x00) => Instruction::SkipNotEqual(x, y),
+        (0x0a, _, _, _) => Instruction::LoadI(nnn),
+        (0x0b, _, _, _) => Instruction::JumpOffset(x, nnn),
+        (0x0c, _, _, _) => Instruction::Random(x, kk),
+        (0x0d, _, _, _) => Instruction::Draw(x, y, n),
+        (0x0e, _, 0x09, 0x0e) => Instruction::SkipKeyPressed(x),
+        (0x0e, _, 0x0a, 0x01) => Instruction::SkipKeyNotPressed(x),
+        (0x0f, 0x00, 0x00, 0x00) => Instruction::LoadLongI(operand as usize),
+        (0x0f, _, 0x00, 0x01) => Instruction::SelectPlanes(x),
+        (0x0f, 0x00, 0x00, 0x02) => Instruction::LoadAudio,
+        (0x0f, _, 0x00, 0x07) => Instruction::GetDelay(x),
+        (0x0f, _, 0x00, 0x0a) => Instruction::WaitKey(x),
+        (0x0f, _, 0x01, 0x05) => Instruction::SetDelay(x),
+        (0x0f, _, 0x01, 0x08) => Instruction::SetSound(x),
+        (0x0f, _, 0x01, 0x0e) => Instruction::AddI(x),
+        (0x0f, _, 0x02, 0x09) => Instruction::Font(x),
+        (0x0f, _, 0x03, 0x00) => Instruction::BigFont(x),
+        (0x0f, _, 0x03, 0x03) => Instruction::Bcd(x),
+        (0x0f, _, 0x03, 0x0a) => Instruction::SetPitch(x),
+        (0x0f, _, 0x05, 0x05) => Instruction::Store(x),
+        (0x0f, _, 0x06, 0x05) => Instruction::Load(x),
+        (0x0f, _, 0x07, 0x05) => Instruction::SaveFlags(x),
+        (0x0f, _, 0x08, 0x05) => Instruction::LoadFlags(x),
+
+        _ => Instruction::Unknown(opcode)
+    }
+}
+
+/// Decodes the instruction at `address`, or `None` if it runs past the end of `memory`.
+pub fn decode_at(memory: &[u8], address: usize) -> Option<Instruction> {
+    let word = |address: usize| -> Option<u16> {
+        let high = *memory.get(address)?;
+        let low = *memory.get(address + 1)?;
+        Some((high as u16) << 8 | low as u16)
+    };
+
+    let opcode = word(address)?;
+    let operand = if is_long(opcode) { word(address + OPCODE_SIZE)? } else { 0 };
+    Some(decode(opcode, operand))
+}
+
+impl Instruction {
+    /// Size in bytes, including the operand word of `F000 NNNN`.
+    pub fn size(&self) -> usize {
+        match *self {
+            Instruction::LoadLongI(_) => 2 * OPCODE_SIZE,
+            _ => OPCODE_SIZE,
+        }
+    }
+
+    /// Time the COSMAC VIP interpreter takes to fetch and run the instruction, in machine
+    /// cycles of 8 clock periods at 1.76 MHz; `skipped` adds the cost of a taken skip. A sprite
+    /// draw also waits for the next frame on top of this. Instructions the VIP never had are
+    /// charged like `7XKK`.
+    pub fn vip_cycles(&self, skipped: bool) -> usize {
+        let skip = if skipped { VIP_SKIP_CYCLES } else { 0 };
+
+        VIP_FETCH_CYCLES + match *self {
+            // The clear loop zeroes the 256 bytes of display memory.
+            Instruction::Clear => 24 + 3078,
+            Instruction::Return => 10,
+            Instruction::Jump(_) => 12,
+            Instruction::Call(_) => 26,
+            Instruction::SkipEqualByte(..) |
+            Instruction::SkipNotEqualByte(..) => 10 + skip,
+            Instruction::SkipEqual(..) |
+            Instruction::SkipNotEqual(..) |
+            Instruction::SkipKeyPressed(_) |
+            Instruction::SkipKeyNotPressed(_) => 14 + skip,
+            Instruction::LoadByte(..) => 6,
+            Instruction::AddByte(..) => 10,
+            Instruction::Move(..) |
+            Instruction::Or(..) |
+            Instruction::And(..) |
+            Instruction::Xor(..) |
+            Instruction::Add(..) |
+            Instruction::Sub(..) |
+            Instruction::ShiftRight(..) |
+            Instruction::SubReverse(..) |
+            Instruction::ShiftLeft(..) => 20,
+            Instruction::LoadI(_) => 12,
+            Instruction::JumpOffset(..) => 22,
+            Instruction::Random(..) => 36,
+            Instruction::Draw(..) => 22,
+            Instruction::GetDelay(_) |
+            Instruction::SetDelay(_) |
+            Instruction::SetSound(_) => 10,
+            // Only the setup; the wait itself is spent blocked in `FX0A`.
+            Instruction::WaitKey(_) => 10,
+            Instruction::AddI(_) |
+            Instruction::Font(_) => 16,
+            // Three divisions by repeated subtraction.
+            Instruction::Bcd(_) => 80,
+            // One pass of the copy loop per register.
+            Instruction::Store(x) |
+            Instruction::Load(x) => 14 + 14 * (x + 1),
+            Instruction::ScrollDown(_) |
+            Instruction::ScrollUp(_) |
+            Instruction::ScrollRight |
+            Instruction::ScrollLeft |
+            Instruction::Exit |
+            Instruction::LowRes |
+            Instruction::HighRes |
+            Instruction::SaveRange(..) |
+            Instruction::LoadRange(..) |
+            Instruction::LoadLongI(_) |
+            Instruction::SelectPlanes(_) |
+            Instruction::LoadAudio |
+            Instruction::BigFont(_) |
+            Instruction::SetPitch(_) |
+            Instruction::SaveFlags(_) |
+            Instruction::LoadFlags(_) |
+            Instruction::Unknown(_) => 10,
+        }
+    }
+
+    /// The address this instruction jumps to, calls or points I at, if any.
+    pub fn target(&self) -> Option<usize> {
+        match *self {
+            Instruction::Jump(nnn) |
+            Instruction::Call(nnn) |
+            Instruction::JumpOffset(_, nnn) |
+            Instruction::LoadI(nnn) |
+            Instruction::LoadLongI(nnn) => Some(nnn),
+            _ => None,
+        }
+    }
+
+    /// Formats the instruction, naming addresses with `address` (e.g. to substitute labels).
+    pub fn mnemonic<F: Fn(usize) -> String>(&self, address: F) -> String {
+        match *self {
+            Instruction::ScrollDown(n) => format!("SCD {}", n),
+            Instruction::ScrollUp(n) => format!("SCU {}", n),
+            Instruction::Clear => "CLS".to_string(),
+            Instruction::Return => "RET".to_string(),
+            Instruction::ScrollRight => "SCR".to_string(),
+            Instruction::ScrollLeft => "SCL".to_string(),
+            Instruction::Exit => "EXIT".to_string(),
+            Instruction::LowRes => "LOW".to_string(),
+            Instruction::HighRes => "HIGH".to_string(),
+            Instruction::Jump(nnn) => format!("JP {}", address(nnn)),
+            Instruction::Call(nnn) => format!("CALL {}", address(nnn)),
+            Instruction::SkipEqualByte(x, kk) => format!("SE V{:X}, {:#04X}", x, kk),
+            Instruction::SkipNotEqualByte(x, kk) => format!("SNE V{:X}, {:#04X}", x, kk),
+            Instruction::SkipEqual(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => format!("SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange(x, y) => format!("LOAD V{:X}, V{:X}", x, y),
+            Instruction::LoadByte(x, kk) => format!("LD V{:X}, {:#04X}", x, kk),
+            Instruction::AddByte(x, kk) => format!("ADD V{:X}, {:#04X}", x, kk),
+            Instruction::Move(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            Instruction::Add(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNotEqual(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(nnn) => format!("LD I, {}", address(nnn)),
+            Instruction::JumpOffset(_, nnn) => format!("JP V0, {}", address(nnn)),
+            Instruction::Random(x, kk) => format!("RND V{:X}, {:#04X}", x, kk),
+            Instruction::Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed(x) => format!("SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed(x) => format!("SKNP V{:X}", x),
+            Instruction::LoadLongI(nnnn) => format!("LD I, LONG {}", address(nnnn)),
+            Instruction::SelectPlanes(n) => format!("PLANE {}", n),
+            Instruction::LoadAudio => "AUDIO".to_string(),
+            Instruction::GetDelay(x) => format!("LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => format!("LD V{:X}, K", x),
+            Instruction::SetDelay(x) => format!("LD DT, V{:X}", x),
+            Instruction::SetSound(x) => format!("LD ST, V{:X}", x),
+            Instruction::AddI(x) => format!("ADD I, V{:X}", x),
+            Instruction::Font(x) => format!("LD F, V{:X}", x),
+            Instruction::BigFont(x) => format!("LD HF, V{:X}", x),
+            Instruction::Bcd(x) => format!("LD B, V{:X}", x),
+            Instruction::SetPitch(x) => format!("PITCH V{:X}", x),
+            Instruction::Store(x) => format!("LD [I], V{:X}", x),
+            Instruction::Load(x) => format!("LD V{:X}, [I]", x),
+            Instruction::SaveFlags(x) => format!("LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => format!("LD V{:X}, R", x),
+            Instruction::Unknown(opcode) => format!("DW {:#06X}", opcode),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic(|address| format!("{:#05X}", address)))
+    }
+}
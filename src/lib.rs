@@ -0,0 +1,37 @@
+extern crate toml;
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+pub mod config;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod filter;
+pub mod framebuffer;
+pub mod frontend;
+pub mod headless;
+pub mod instruction;
+pub mod memory;
+pub mod movie;
+pub mod palette;
+pub mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod savestate;
+pub mod viewport;
+
+#[cfg(feature = "sdl")]
+pub mod display;
+#[cfg(feature = "sdl")]
+pub mod input;
+#[cfg(feature = "sdl")]
+pub mod sound;
+
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+
+pub const SCHIP_WIDTH: usize = 128;
+pub const SCHIP_HEIGHT: usize = 64;
+
+pub use framebuffer::Framebuffer;
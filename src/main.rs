@@ -1,51 +1,480 @@
-extern crate sdl2;
+extern crate chip8;
 extern crate rand;
+#[cfg(feature = "sdl")]
+extern crate sdl2;
 
-
-mod display;
-mod memory;
-mod sound;
-mod input;
-mod cpu;
-
+use std::env;
+use std::fs;
+use std::path::Path;
 use std::process;
-use std::thread;
-use std::time::Duration;
 
-const CHIP8_WIDTH: usize = 64;
-const CHIP8_HEIGHT: usize = 32;
+use chip8::config::{self, Config};
+use chip8::cpu;
+use chip8::cpu::{Strictness, Timing};
+use chip8::debugger;
+use chip8::disasm;
+use chip8::error::EmulatorError;
+use chip8::filter::FilterMode;
+use chip8::frontend::{self, AudioSink, KeypadSource, NullKeypad, VideoSink};
+use chip8::headless::{self, Limit, Script};
+use chip8::memory;
+use chip8::movie::{Movie, Playback, Recorder};
+use chip8::palette::{self, Palette, Rgb};
+use chip8::quirks::Quirks;
+use chip8::random::{self, RandomMode};
+use chip8::rewind::{self, Rewind};
+use chip8::savestate::SaveSlots;
 
 pub fn main() {
 
-    let mut memory = memory::RAM::new();
+    let mut args = env::args().skip(1).peekable();
+
+    if args.peek().map(String::as_str) == Some("disasm") {
+        args.next();
+        disassemble(args);
+        return;
+    }
 
-    memory.load_rom().unwrap_or_else(|err| {
+    let mut options = parse_args(args).unwrap_or_else(|err| {
         eprintln!("Error: {}", err);
         process::exit(1);
     });
 
-    let sleep_duration = Duration::from_millis(2);
-    let sdl_context = sdl2::init().unwrap();
-    let mut display = display::Display::new(&sdl_context);
-    let mut input = input::Input::new(&sdl_context);
-    let sound = sound::Sound::new(&sdl_context);
-    let mut cpu = cpu::CPU::new(memory.memory);
+    // A movie replays under the settings it was recorded with.
+    let playback = options.play_path.as_ref().map(|path| {
+        Movie::load(path).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        })
+    });
+    if let Some(ref movie) = playback {
+        options.seed = movie.seed;
+        options.quirks = movie.quirks;
+        options.random_mode = movie.random_mode;
+        options.cycles_per_frame = movie.cycles_per_frame;
+        options.timing = movie.timing;
+        options.memory_size = movie.memory_size;
+    }
 
-    while let Ok(keypad) = input.poll() {
+    let mut memory = memory::RAM::with_size(options.memory_size);
+
+    memory.load_rom(&options.rom_path).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
 
-        let output = cpu.cpu_cycle(keypad);
+    let mut cpu = cpu::CPU::new(memory.memory, options.quirks, options.seed);
+    cpu.set_cycles_per_frame(options.cycles_per_frame);
+    cpu.set_timing(options.timing);
+    cpu.set_strictness(options.strictness);
+    cpu.set_random_mode(options.random_mode);
+    if options.random_mode == RandomMode::CosmacVip {
+        let page = load_vip_page(options.vip_interpreter.as_ref()).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        });
+        cpu.set_vip_page(&page);
+    }
 
-        if output.video_memory_changed {
-            display.draw(output.video_memory);
+    let rpl_path = format!("{}.rpl", options.rom_path);
+    let mut rpl_flags = [0; cpu::RPL_FLAG_COUNT];
+    // Movies always start from cleared flags so that they replay the same way anywhere.
+    let movie_active = playback.is_some() || options.record_path.is_some();
+    if let (false, Ok(saved)) = (movie_active, fs::read(&rpl_path)) {
+        for (flag, &byte) in rpl_flags.iter_mut().zip(saved.iter()) {
+            *flag = byte;
         }
+    }
+    cpu.set_rpl_flags(rpl_flags);
+
+    let result = match options.limit {
+        Some(limit) => run_headless(&mut cpu, &options, playback, limit),
+        None => run(&mut cpu, &options, &load_config(&options), playback),
+    };
+
+    if cpu.rpl_flags() != rpl_flags {
+        fs::write(&rpl_path, cpu.rpl_flags()).unwrap_or_else(|err| {
+            eprintln!("Error: could not save RPL flags: {}", err);
+        });
+    }
+
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn disassemble<I: Iterator<Item = String>>(mut args: I) {
+    let rom_path = args.next().unwrap_or_else(|| {
+        eprintln!("Error: file path to the rom is required");
+        process::exit(1);
+    });
+
+    let rom = fs::read(&rom_path).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
 
-        if output.beep {
-            sound.start_beep();
+    print!("{}", disasm::disassemble(&rom));
+}
+
+struct Options {
+    rom_path: String,
+    quirks: Quirks,
+    memory_size: usize,
+    cycles_per_frame: usize,
+    timing: Timing,
+    strictness: Strictness,
+    seed: u64,
+    random_mode: RandomMode,
+    vip_interpreter: Option<String>,
+    rewind_budget: usize,
+    record_path: Option<String>,
+    play_path: Option<String>,
+    limit: Option<Limit>,
+    input_path: Option<String>,
+    screenshot_path: Option<String>,
+    config_path: Option<String>,
+    palette: Option<Palette>,
+    colours: Option<Vec<Rgb>>,
+    scale: Option<u32>,
+    integer_scale: bool,
+    fullscreen: bool,
+    vsync: bool,
+    filter: Option<FilterMode>,
+    decay: Option<f32>,
+    blend_frames: Option<usize>,
+    debug: bool,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut rom_path = None;
+    let mut quirks = Quirks::default();
+    let mut preset = None;
+    let mut memory_size = None;
+    let mut cycles_per_frame = cpu::DEFAULT_CYCLES_PER_FRAME;
+    let mut timing = Timing::Fixed;
+    let mut strictness = Strictness::Ignore;
+    let mut seed = rand::random();
+    let mut random_mode = RandomMode::Xorshift;
+    let mut vip_interpreter = None;
+    let mut rewind_budget = rewind::DEFAULT_BUDGET;
+    let mut record_path = None;
+    let mut play_path = None;
+    let mut limit = None;
+    let mut input_path = None;
+    let mut screenshot_path = None;
+    let mut wait_key_release = None;
+    let mut config_path = None;
+    let mut palette = None;
+    let mut colours = None;
+    let mut scale = None;
+    let mut integer_scale = false;
+    let mut fullscreen = false;
+    let mut vsync = false;
+    let mut filter = None;
+    let mut decay = None;
+    let mut blend_frames = None;
+    let mut debug = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quirks" => {
+                let name = args.next().ok_or("--quirks requires a preset name")?;
+                quirks = Quirks::from_name(&name)
+                    .ok_or_else(|| format!("unknown quirks preset '{}'", name))?;
+                preset = Some(name);
+            }
+            "--memory" => {
+                let value = args.next().ok_or("--memory requires 4 or 64")?;
+                memory_size = match value.as_str() {
+                    "4" => Some(memory::MEMORY_SIZE),
+                    "64" => Some(memory::XO_MEMORY_SIZE),
+                    _ => return Err(format!("invalid memory size '{}', expected 4 or 64 KiB", value)),
+                };
+            }
+            "--cycles-per-frame" => {
+                let value = args.next().ok_or("--cycles-per-frame requires a number")?;
+                cycles_per_frame = value.parse()
+                    .map_err(|_| format!("invalid cycles per frame '{}'", value))?;
+            }
+            "--wait-key" => {
+                let name = args.next().ok_or("--wait-key requires press or release")?;
+                wait_key_release = match name.as_str() {
+                    "press" => Some(false),
+                    "release" => Some(true),
+                    _ => return Err(format!("unknown key wait '{}'", name)),
+                };
+            }
+            "--timing" => {
+                let name = args.next().ok_or("--timing requires fixed or vip")?;
+                timing = Timing::from_name(&name)
+                    .ok_or_else(|| format!("unknown timing '{}'", name))?;
+            }
+            "--strictness" => {
+                let name = args.next().ok_or("--strictness requires ignore, log or halt")?;
+                strictness = Strictness::from_name(&name)
+                    .ok_or_else(|| format!("unknown strictness '{}'", name))?;
+            }
+            "--seed" => {
+                let value = args.next().ok_or("--seed requires a number")?;
+                seed = value.parse().map_err(|_| format!("invalid seed '{}'", value))?;
+            }
+            "--random" => {
+                let name = args.next().ok_or("--random requires xorshift or vip")?;
+                random_mode = RandomMode::from_name(&name)
+                    .ok_or_else(|| format!("unknown random mode '{}'", name))?;
+            }
+            "--vip-interpreter" => {
+                vip_interpreter = Some(args.next().ok_or("--vip-interpreter requires a file path")?);
+            }
+            "--rewind-memory" => {
+                let value = args.next().ok_or("--rewind-memory requires a size in megabytes")?;
+                let megabytes: usize = value.parse()
+                    .map_err(|_| format!("invalid rewind memory '{}'", value))?;
+                rewind_budget = megabytes * 1024 * 1024;
+            }
+            "--record" => record_path = Some(args.next().ok_or("--record requires a movie path")?),
+            "--play" => play_path = Some(args.next().ok_or("--play requires a movie path")?),
+            "--frames" | "--cycles" => {
+                let value = args.next().ok_or_else(|| format!("{} requires a number", arg))?;
+                let count = value.parse().map_err(|_| format!("invalid count '{}'", value))?;
+                limit = Some(if arg == "--frames" { Limit::Frames(count) } else { Limit::Cycles(count) });
+            }
+            "--input" => input_path = Some(args.next().ok_or("--input requires a script path")?),
+            "--screenshot" => {
+                screenshot_path = Some(args.next().ok_or("--screenshot requires an output path")?);
+            }
+            "--config" => config_path = Some(args.next().ok_or("--config requires a file path")?),
+            "--palette" => {
+                let name = args.next().ok_or("--palette requires a palette name")?;
+                palette = Some(Palette::parse(&name)?);
+            }
+            "--colours" => {
+                let value = args.next().ok_or("--colours requires a list of #rrggbb colours")?;
+                colours = Some(palette::parse_colours(&value)?);
+            }
+            "--scale" => {
+                let value = args.next().ok_or("--scale requires a number")?;
+                scale = match value.parse() {
+                    Ok(scale) if (1..=100).contains(&scale) => Some(scale),
+                    _ => return Err(format!("invalid scale '{}', expected 1 to 100", value)),
+                };
+            }
+            "--integer-scale" => integer_scale = true,
+            "--fullscreen" => fullscreen = true,
+            "--vsync" => vsync = true,
+            "--filter" => {
+                let name = args.next().ok_or("--filter requires off, phosphor or or")?;
+                filter = Some(FilterMode::from_name(&name)
+                    .ok_or_else(|| format!("unknown filter '{}'", name))?);
+            }
+            "--decay" => {
+                let value = args.next().ok_or("--decay requires a number")?;
+                decay = match value.parse() {
+                    Ok(decay) if (0.0..1.0).contains(&decay) => Some(decay),
+                    _ => return Err(format!("invalid decay '{}', expected 0.0 up to 1.0", value)),
+                };
+            }
+            "--blend-frames" => {
+                let value = args.next().ok_or("--blend-frames requires a number")?;
+                blend_frames = match value.parse() {
+                    Ok(frames) if (1..=60).contains(&frames) => Some(frames),
+                    _ => return Err(format!("invalid blend frames '{}', expected 1 to 60", value)),
+                };
+            }
+            "--debug" => debug = true,
+            _ => rom_path = Some(arg),
         }
-        else {
-            sound.stop_beep();
+    }
+
+    if let Some(wait_key_release) = wait_key_release {
+        quirks.wait_key_release = wait_key_release;
+    }
+    let memory_size = memory_size
+        .unwrap_or_else(|| preset.map_or(memory::MEMORY_SIZE, |name| memory::size_for_preset(&name)));
+
+    if record_path.is_some() && play_path.is_some() {
+        return Err("--record and --play cannot be used together".to_string());
+    }
+    if input_path.is_some() && play_path.is_some() {
+        return Err("--input and --play cannot be used together".to_string());
+    }
+    if limit.is_none() && (input_path.is_some() || screenshot_path.is_some()) {
+        return Err("--input and --screenshot need a headless run, use --frames or --cycles".to_string());
+    }
+
+    let rom_path = rom_path.ok_or("file path to the rom is required")?;
+    Ok(Options {
+        rom_path, quirks, memory_size, cycles_per_frame, timing, strictness, seed, random_mode,
+        vip_interpreter, rewind_budget, record_path, play_path, limit, input_path, screenshot_path,
+        config_path, palette, colours, scale, integer_scale, fullscreen, vsync, filter, decay,
+        blend_frames, debug,
+    })
+}
+
+/// The VIP generator reads interpreter code that cannot be shipped, so it has to come from a
+/// dump of the interpreter page made by the user.
+fn load_vip_page(path: Option<&String>) -> Result<Vec<u8>, String> {
+    let path = path.ok_or("the vip random mode needs the interpreter page, pass --vip-interpreter")?;
+    let page = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+    if page.len() != random::VIP_PAGE_SIZE {
+        return Err(format!("{}: the interpreter page must be {} bytes", path, random::VIP_PAGE_SIZE));
+    }
+    Ok(page)
+}
+
+/// Runs with no window, audio or SDL, then reports the final state on stdout.
+fn run_headless(cpu: &mut cpu::CPU, options: &Options, playback: Option<Movie>, limit: Limit)
+    -> Result<(), EmulatorError> {
+
+    let summary = match (playback, &options.input_path) {
+        (Some(movie), _) => headless::run(cpu, &mut Playback::new(NullKeypad, movie), limit)?,
+        (None, Some(path)) => {
+            let script = Script::load(path).unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            });
+            run_headless_recording(cpu, options, script, limit)?
+        }
+        (None, None) => run_headless_recording(cpu, options, NullKeypad, limit)?,
+    };
+
+    if let Some(ref path) = options.screenshot_path {
+        fs::write(path, headless::pbm(cpu.video_memory()))
+            .map_err(|err| EmulatorError::Io(err.to_string()))?;
+    }
+
+    print!("{}", headless::report(cpu, &summary));
+    Ok(())
+}
+
+/// Records the keypad into a movie when one was asked for, so that a scripted run can be
+/// replayed with `--play`.
+fn run_headless_recording<K: KeypadSource>(cpu: &mut cpu::CPU, options: &Options, mut keypad: K,
+                                           limit: Limit) -> Result<headless::Summary, EmulatorError> {
+    match options.record_path {
+        Some(ref path) => {
+            let movie = Movie::new(options.seed, options.quirks, options.memory_size,
+                                   options.random_mode, options.cycles_per_frame, options.timing);
+            let mut recorder = Recorder::new(keypad, movie);
+            let summary = headless::run(cpu, &mut recorder, limit)?;
+
+            recorder.movie().save(path)?;
+            Ok(summary)
         }
-        
-        thread::sleep(sleep_duration);
+        None => headless::run(cpu, &mut keypad, limit),
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn run(cpu: &mut cpu::CPU, options: &Options, config: &Config, playback: Option<Movie>)
+    -> Result<(), EmulatorError> {
+    use chip8::{display, input, sound};
+
+    let sdl_context = sdl2::init().unwrap();
+    let canvas = display::create_canvas(&sdl_context, config);
+    let texture_creator = canvas.texture_creator();
+    let mut display = display::Display::new(canvas, &texture_creator, config);
+    let input = input::Input::new(&sdl_context, config).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
+    let mut sound = sound::Sound::new(&sdl_context);
+
+    run_with_movie(cpu, options, playback, &mut display, &mut sound, input)
+}
+
+/// Reads `--config`, or `chip8.toml` when it exists, falling back to the built-in defaults.
+///
+/// Display options given on the command line take precedence over the file.
+fn load_config(options: &Options) -> Config {
+    let path = match options.config_path {
+        Some(ref path) => Some(path.as_str()),
+        None if Path::new(config::DEFAULT_PATH).exists() => Some(config::DEFAULT_PATH),
+        None => None,
+    };
+
+    let mut config = path.map_or_else(Config::default, |path| {
+        Config::load(path, &options.rom_path).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        })
+    });
+
+    if let Some(palette) = options.palette {
+        config.palette = palette;
+    }
+    if let Some(ref colours) = options.colours {
+        config.palette = config.palette.with_colours(colours);
+    }
+    if let Some(scale) = options.scale {
+        config.scale = scale;
+    }
+    config.integer_scale |= options.integer_scale;
+    config.fullscreen |= options.fullscreen;
+    config.vsync |= options.vsync;
+    if let Some(filter) = options.filter {
+        config.filter = filter;
+    }
+    if let Some(decay) = options.decay {
+        config.decay = decay;
+    }
+    if let Some(blend_frames) = options.blend_frames {
+        config.blend_frames = blend_frames;
+    }
+
+    config
+}
+
+/// Without SDL only the debugger, which needs no window, can drive the CPU.
+#[cfg(not(feature = "sdl"))]
+fn run(cpu: &mut cpu::CPU, options: &Options, _config: &Config, playback: Option<Movie>)
+    -> Result<(), EmulatorError> {
+    use chip8::frontend::{NullAudio, NullVideo};
+
+    if !options.debug {
+        eprintln!("Error: built without the `sdl` feature, no frontend is available");
+        process::exit(1);
+    }
+
+    run_with_movie(cpu, options, playback, &mut NullVideo, &mut NullAudio, NullKeypad)
+}
+
+/// Puts a movie recorder or player in front of the keypad when one was asked for.
+fn run_with_movie<V, A, K>(cpu: &mut cpu::CPU, options: &Options, playback: Option<Movie>,
+                           video: &mut V, audio: &mut A, mut keypad: K)
+    -> Result<(), EmulatorError>
+    where V: VideoSink, A: AudioSink, K: KeypadSource {
+
+    if let Some(movie) = playback {
+        return drive(cpu, options, video, audio, &mut Playback::new(keypad, movie));
+    }
+
+    match options.record_path {
+        Some(ref path) => {
+            let movie = Movie::new(options.seed, options.quirks, options.memory_size,
+                                   options.random_mode, options.cycles_per_frame, options.timing);
+            let mut recorder = Recorder::new(keypad, movie);
+            let result = drive(cpu, options, video, audio, &mut recorder);
+
+            recorder.movie().save(path)?;
+            result
+        }
+        None => drive(cpu, options, video, audio, &mut keypad),
+    }
+}
+
+fn drive<V, A, K>(cpu: &mut cpu::CPU, options: &Options, video: &mut V, audio: &mut A,
+                  keypad: &mut K) -> Result<(), EmulatorError>
+    where V: VideoSink, A: AudioSink, K: KeypadSource {
+
+    if options.debug {
+        debugger::run(cpu, video, audio, keypad)
+    }
+    else {
+        let mut slots = SaveSlots::new(&options.rom_path);
+        let mut rewind = Rewind::new(options.rewind_budget);
+        frontend::run(cpu, video, audio, keypad, &mut slots, &mut rewind)
     }
 }
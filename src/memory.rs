@@ -1,46 +1,68 @@
-use std::env;
 use std::fs::File;
-use std::error::Error;
 use std::io::prelude::*;
 
+use error::EmulatorError;
+
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+
+/// The memory a quirks preset's platform had: 64 KiB for XO-CHIP, 4 KiB for everything else.
+pub fn size_for_preset(name: &str) -> usize {
+    match name {
+        "xochip" => XO_MEMORY_SIZE,
+        _ => MEMORY_SIZE,
+    }
+}
+
+pub const BIG_FONT_ADDRESS: usize = 0x50;
+
+pub const PROGRAM_START: usize = 0x200;
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct RAM {
-    pub memory : [u8; 4096],
+    pub memory : Vec<u8>,
 }
 
 impl RAM {
     pub fn new() -> RAM {
+        RAM::with_size(MEMORY_SIZE)
+    }
+
+    /// Creates RAM of `size` bytes, e.g. `XO_MEMORY_SIZE` for XO-CHIP programs.
+    pub fn with_size(size: usize) -> RAM {
         let mut ram = RAM {
-            memory: [0; 4096],
+            memory: vec![0; size],
         };
 
-        for i in 0x0..0x50  {
-            ram.memory[i] = FONTSET[i];
-        }
+        ram.memory[..FONTSET.len()].copy_from_slice(&FONTSET);
+        ram.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
         ram
     }
 
-    pub fn load_rom(&mut self) -> Result<(), String> {
+    pub fn load_rom(&mut self, rom_path: &str) -> Result<(), EmulatorError> {
+
+        let mut buffer = Vec::new();
 
-       let args: Vec<String> = env::args().collect();
+        let mut rom = File::open(rom_path).map_err(|e| EmulatorError::Io(e.to_string()))?;
+        rom.read_to_end(&mut buffer).map_err(|e| EmulatorError::Io(e.to_string()))?;
 
-        if args.len() <= 1 {
-            return  Err("file path to the rom is required".to_string());
+        let max = self.memory.len() - PROGRAM_START;
+        if buffer.len() > max {
+            return Err(EmulatorError::RomTooLarge { size: buffer.len(), max });
         }
 
-        let rom_path = &args[1];
-        let mut buffer = Vec::new();
-        
-        let mut rom = File::open(rom_path).map_err(|e| e.description().to_string())?;
-        rom.read_to_end(&mut buffer).map_err(|e| e.description().to_string())?;
+        self.memory[PROGRAM_START..PROGRAM_START + buffer.len()].copy_from_slice(&buffer);
 
-        for (i, byte) in buffer.bytes().enumerate() {
-            self.memory[i+0x200] = byte.map_err(|e| e.description().to_string())?;
-        }
-        
         Ok(())
     }
 }
 
+impl Default for RAM {
+    fn default() -> Self {
+        RAM::new()
+    }
+}
+
 static FONTSET: [u8; 80] =
     [0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70,
      0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0,
@@ -50,3 +72,21 @@ static FONTSET: [u8; 80] =
      0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0,
      0xF0, 0x80, 0x80, 0x80, 0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0,
      0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80];
+
+static BIG_FONTSET: [u8; 160] =
+    [0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,
+     0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,
+     0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+     0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+     0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,
+     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+     0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+     0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+     0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+     0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,
+     0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,
+     0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,
+     0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,
+     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0];
@@ -0,0 +1,274 @@
+use std::fs;
+
+use cpu::Timing;
+use error::EmulatorError;
+use frontend::{Hotkey, KeypadSource};
+use quirks::Quirks;
+use random::RandomMode;
+use savestate::{StateReader, StateWriter};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+
+pub const VERSION: u16 = 1;
+
+/// Everything needed to replay a run exactly: the settings the CPU was created with and
+/// the keypad passed to every frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub memory_size: usize,
+    pub random_mode: RandomMode,
+    pub cycles_per_frame: usize,
+    pub timing: Timing,
+    pub frames: Vec<[bool; 16]>,
+}
+
+impl Movie {
+    pub fn new(seed: u64, quirks: Quirks, memory_size: usize, random_mode: RandomMode,
+               cycles_per_frame: usize, timing: Timing) -> Self {
+        Movie { seed, quirks, memory_size, random_mode, cycles_per_frame, timing, frames: Vec::new() }
+    }
+
+    pub fn load(path: &str) -> Result<Self, EmulatorError> {
+        let bytes = fs::read(path).map_err(|e| EmulatorError::Io(e.to_string()))?;
+        Movie::from_bytes(&bytes)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), EmulatorError> {
+        fs::write(path, self.to_bytes()).map_err(|e| EmulatorError::Io(e.to_string()))
+    }
+
+    /// The header is followed by one little-endian bitmask of pressed keys per frame.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let quirks = [
+            self.quirks.shift_uses_vy,
+            self.quirks.load_store_increments_i,
+            self.quirks.jump_uses_vx,
+            self.quirks.clip_sprites,
+            self.quirks.logic_resets_vf,
+            self.quirks.display_wait,
+            self.quirks.wait_key_release,
+            self.quirks.load_store_increments_by_x,
+        ];
+        let quirk_bits = quirks.iter().enumerate()
+            .fold(0u8, |bits, (i, &set)| bits | (set as u8) << i);
+        let random_mode = match self.random_mode {
+            RandomMode::Xorshift => 0u8,
+            RandomMode::CosmacVip => 1,
+        };
+        let timing = match self.timing {
+            Timing::Fixed => 0u8,
+            Timing::CosmacVip => 1,
+        };
+
+        let mut writer = StateWriter::with_header(MAGIC, VERSION);
+        writer.u64(self.seed);
+        writer.u8(quirk_bits);
+        writer.u8(random_mode);
+        writer.u32(self.cycles_per_frame as u32);
+        writer.u32(self.frames.len() as u32);
+        writer.u8(timing);
+        writer.u32(self.memory_size as u32);
+
+        for keys in &self.frames {
+            let mask = keys.iter().enumerate()
+                .fold(0u16, |mask, (key, &pressed)| mask | (pressed as u16) << key);
+            writer.u16(mask);
+        }
+
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EmulatorError> {
+        let invalid = |reason: &str| EmulatorError::InvalidMovie(reason.to_string());
+        let mut reader = StateReader::with_header(bytes, MAGIC, VERSION, EmulatorError::InvalidMovie)?;
+
+        let seed = reader.u64()?;
+        let quirk_bits = reader.u8()?;
+        let random_mode = match reader.u8()? {
+            0 => RandomMode::Xorshift,
+            1 => RandomMode::CosmacVip,
+            _ => return Err(invalid("unknown random mode")),
+        };
+        let cycles_per_frame = reader.u32()?;
+        let frame_count = reader.u32()? as usize;
+
+        let timing = match reader.u8()? {
+            0 => Timing::Fixed,
+            1 => Timing::CosmacVip,
+            _ => return Err(invalid("unknown timing")),
+        };
+        let memory_size = reader.u32()? as usize;
+
+        let frames = (0..frame_count)
+            .map(|_| {
+                let mask = reader.u16()?;
+                let mut keys = [false; 16];
+                for (key, pressed) in keys.iter_mut().enumerate() {
+                    *pressed = mask & (1 << key) != 0;
+                }
+                Ok(keys)
+            })
+            .collect::<Result<Vec<_>, EmulatorError>>()
+            .map_err(|_| invalid("frame count does not match the file length"))?;
+        if !reader.is_empty() {
+            return Err(invalid("frame count does not match the file length"));
+        }
+
+        let quirk = |i: u8| quirk_bits & (1 << i) != 0;
+        Ok(Movie {
+            seed,
+            quirks: Quirks {
+                shift_uses_vy: quirk(0),
+                load_store_increments_i: quirk(1),
+                jump_uses_vx: quirk(2),
+                clip_sprites: quirk(3),
+                logic_resets_vf: quirk(4),
+                display_wait: quirk(5),
+                wait_key_release: quirk(6),
+                load_store_increments_by_x: quirk(7),
+            },
+            memory_size,
+            random_mode,
+            cycles_per_frame: cycles_per_frame as usize,
+            timing,
+            frames,
+        })
+    }
+}
+
+/// Passes another keypad source through while logging every frame it reports.
+///
+/// Frames that were rewound instead of run are dropped, along with the frames stepped back
+/// over. Loading a save state would leave the movie out of step with the CPU, so that hotkey
+/// is swallowed while recording.
+pub struct Recorder<K> {
+    inner: K,
+    movie: Movie,
+    hotkeys: Vec<Hotkey>,
+}
+
+impl<K: KeypadSource> Recorder<K> {
+    pub fn new(inner: K, movie: Movie) -> Self {
+        Recorder { inner, movie, hotkeys: Vec::new() }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+}
+
+impl<K: KeypadSource> KeypadSource for Recorder<K> {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        let keys = self.inner.poll()?;
+
+        self.hotkeys = self.inner.hotkeys();
+        self.hotkeys.retain(|&hotkey| hotkey != Hotkey::LoadState);
+        self.movie.frames.push(keys);
+
+        Some(keys)
+    }
+
+    fn hotkeys(&mut self) -> Vec<Hotkey> {
+        self.hotkeys.drain(..).collect()
+    }
+
+    fn rewound(&mut self, stepped_back: bool) {
+        self.movie.frames.pop();
+        if stepped_back {
+            self.movie.frames.pop();
+        }
+        self.inner.rewound(stepped_back);
+    }
+}
+
+/// Feeds a recorded movie back in, ending the run after its last frame.
+///
+/// The wrapped source is still polled so that the user can quit, but its keys and
+/// hotkeys are ignored.
+pub struct Playback<K> {
+    inner: K,
+    frames: Vec<[bool; 16]>,
+    next_frame: usize,
+}
+
+impl<K: KeypadSource> Playback<K> {
+    pub fn new(inner: K, movie: Movie) -> Self {
+        Playback { inner, frames: movie.frames, next_frame: 0 }
+    }
+}
+
+impl<K: KeypadSource> KeypadSource for Playback<K> {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        self.inner.poll()?;
+        self.inner.hotkeys();
+
+        let keys = self.frames.get(self.next_frame).cloned()?;
+        self.next_frame += 1;
+        Some(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frontend::NullKeypad;
+    use memory;
+
+    fn movie() -> Movie {
+        let mut movie = Movie::new(0x0123_4567_89ab_cdef, Quirks::chip48(), memory::XO_MEMORY_SIZE,
+                                   RandomMode::CosmacVip, 15, Timing::CosmacVip);
+        let mut keys = [false; 16];
+        movie.frames.push(keys);
+        keys[0x1] = true;
+        keys[0xf] = true;
+        movie.frames.push(keys);
+        movie
+    }
+
+    #[test]
+    fn round_trips() {
+        let movie = movie();
+        assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie));
+    }
+
+    #[test]
+    fn keeps_the_version_1_layout() {
+        let mut expected = b"C8MV\x01\x00".to_vec();
+        expected.extend_from_slice(&[0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01]);
+        expected.extend_from_slice(&[0b1100_1110, 1, 15, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 1, 0]);
+        expected.extend_from_slice(&[0x00, 0x00, 0x02, 0x80]);
+
+        assert_eq!(movie().to_bytes(), expected);
+    }
+
+    #[test]
+    fn rejects_a_wrong_frame_count() {
+        let mut bytes = movie().to_bytes();
+        bytes.pop();
+        assert!(Movie::from_bytes(&bytes).is_err());
+
+        let mut bytes = movie().to_bytes();
+        bytes.push(0);
+        assert!(Movie::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn recorder_drops_rewound_frames() {
+        let movie = Movie::new(0, Quirks::default(), memory::MEMORY_SIZE, RandomMode::Xorshift, 10,
+                               Timing::Fixed);
+        let mut recorder = Recorder::new(NullKeypad, movie);
+        for _ in 0..3 {
+            recorder.poll();
+        }
+
+        recorder.poll();
+        recorder.rewound(true);
+        assert_eq!(recorder.movie().frames.len(), 2);
+
+        recorder.poll();
+        recorder.rewound(false);
+        assert_eq!(recorder.movie().frames.len(), 2);
+    }
+}
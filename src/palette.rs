@@ -0,0 +1,135 @@
+/// A colour as red, green and blue components.
+pub type Rgb = (u8, u8, u8);
+
+/// Names accepted by `Palette::from_name`, in the order the cycling hotkey visits them.
+pub const NAMES: &[&str] = &["classic", "green", "amber", "lcd", "octo"];
+
+/// The colour shown for each pixel value: the background, plane 1, plane 2 and both planes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colours: [Rgb; 4],
+}
+
+impl Palette {
+    pub fn classic() -> Self {
+        Palette {
+            colours: [(0x00, 0x00, 0x00), (0xff, 0xff, 0xff), (0xaa, 0xaa, 0xaa), (0x55, 0x55, 0x55)],
+        }
+    }
+
+    /// Green phosphor on a dark tube.
+    pub fn green() -> Self {
+        Palette {
+            colours: [(0x0a, 0x14, 0x0a), (0x33, 0xff, 0x66), (0x1a, 0x80, 0x33), (0x99, 0xff, 0xb3)],
+        }
+    }
+
+    /// Amber phosphor, easier on the eyes than white over long sessions.
+    pub fn amber() -> Self {
+        Palette {
+            colours: [(0x1a, 0x0f, 0x00), (0xff, 0xb0, 0x00), (0x99, 0x66, 0x00), (0xff, 0xd9, 0x80)],
+        }
+    }
+
+    /// Dark pixels on a pale green handheld LCD.
+    pub fn lcd() -> Self {
+        Palette {
+            colours: [(0x9b, 0xbc, 0x0f), (0x0f, 0x38, 0x0f), (0x8b, 0xac, 0x0f), (0x30, 0x62, 0x30)],
+        }
+    }
+
+    /// The defaults of the Octo IDE.
+    pub fn octo() -> Self {
+        Palette {
+            colours: [(0x99, 0x66, 0x00), (0xff, 0xcc, 0x00), (0xff, 0x66, 0x00), (0x66, 0x22, 0x00)],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Palette::classic()),
+            "green" => Some(Palette::green()),
+            "amber" => Some(Palette::amber()),
+            "lcd" => Some(Palette::lcd()),
+            "octo" => Some(Palette::octo()),
+            _ => None,
+        }
+    }
+
+    /// Like `from_name`, with an error listing the names on failure.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Palette::from_name(name)
+            .ok_or_else(|| format!("unknown palette '{}', expected one of {}", name, NAMES.join(", ")))
+    }
+
+    /// Replaces the first colours with the given ones, keeping the rest.
+    pub fn with_colours(mut self, colours: &[Rgb]) -> Self {
+        for (colour, &new) in self.colours.iter_mut().zip(colours) {
+            *colour = new;
+        }
+        self
+    }
+
+    pub fn colour(&self, pixel: u8) -> Rgb {
+        self.colours[pixel as usize & 0b11]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::classic()
+    }
+}
+
+/// Parses a `#rrggbb` colour, with the `#` optional.
+pub fn parse_colour(text: &str) -> Result<Rgb, String> {
+    let hex = text.trim_start_matches('#');
+    let value = match u32::from_str_radix(hex, 16) {
+        Ok(value) if hex.len() == 6 => value,
+        _ => return Err(format!("'{}' is not a #rrggbb colour", text)),
+    };
+
+    Ok(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+/// Parses up to four comma-separated colours, as taken by `--colours`.
+pub fn parse_colours(text: &str) -> Result<Vec<Rgb>, String> {
+    let colours = text.split(',')
+        .map(|colour| parse_colour(colour.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if colours.len() > 4 {
+        return Err("a palette has at most four colours".to_string());
+    }
+    Ok(colours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_name_is_a_palette() {
+        for name in NAMES {
+            assert!(Palette::from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(Palette::from_name("sepia"), None);
+    }
+
+    #[test]
+    fn custom_colours_replace_the_first_entries() {
+        let colours = parse_colours("#102030, ffeedd").unwrap();
+        let palette = Palette::amber().with_colours(&colours);
+
+        assert_eq!(palette.colour(0), (0x10, 0x20, 0x30));
+        assert_eq!(palette.colour(1), (0xff, 0xee, 0xdd));
+        assert_eq!(palette.colour(2), Palette::amber().colour(2));
+        assert_eq!(palette.colour(3), Palette::amber().colour(3));
+    }
+
+    #[test]
+    fn rejects_malformed_colours() {
+        assert!(parse_colour("#fff").is_err());
+        assert!(parse_colour("#gg0000").is_err());
+        assert!(parse_colours("#000000,#111111,#222222,#333333,#444444").is_err());
+    }
+}
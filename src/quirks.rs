@@ -0,0 +1,120 @@
+/// Behaviour of the instructions that differ between CHIP-8 interpreters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift Vy into Vx rather than shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// `FX55`/`FX65` leave I pointing past the last register transferred.
+    pub load_store_increments_i: bool,
+    /// With `load_store_increments_i`, I advances by X rather than X + 1, as on CHIP-48.
+    pub load_store_increments_by_x: bool,
+    /// `BNNN` jumps to NNN + VX, with X taken from the high nibble of NNN.
+    pub jump_uses_vx: bool,
+    /// `DXYN` clips sprites at the screen edges instead of wrapping them.
+    pub clip_sprites: bool,
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// `DXYN` waits for the next vertical blank before execution continues.
+    pub display_wait: bool,
+    /// `FX0A` completes when the key is released rather than as soon as it is pressed.
+    pub wait_key_release: bool,
+}
+
+impl Quirks {
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            load_store_increments_by_x: false,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            logic_resets_vf: true,
+            display_wait: true,
+            wait_key_release: true,
+        }
+    }
+
+    /// Like SUPER-CHIP, except that `FX55`/`FX65` advance I by X.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: true,
+            load_store_increments_by_x: true,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            display_wait: false,
+            wait_key_release: true,
+        }
+    }
+
+    pub fn super_chip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            load_store_increments_by_x: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            display_wait: false,
+            wait_key_release: true,
+        }
+    }
+
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            load_store_increments_by_x: false,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            display_wait: false,
+            wait_key_release: true,
+        }
+    }
+
+    /// Looks up a preset by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vip" | "chip8" => Some(Quirks::cosmac_vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" | "superchip" => Some(Quirks::super_chip()),
+            "xochip" => Some(Quirks::xo_chip()),
+            _ => None,
+        }
+    }
+}
+
+/// How this emulator behaved before the quirks could be chosen, which is closest to SUPER-CHIP
+/// but wraps sprites and adds V0 in `BNNN`.
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            load_store_increments_by_x: false,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            display_wait: false,
+            wait_key_release: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_differ() {
+        let presets = [Quirks::default(), Quirks::cosmac_vip(), Quirks::chip48(), Quirks::super_chip(),
+                       Quirks::xo_chip()];
+
+        for (i, a) in presets.iter().enumerate() {
+            for b in &presets[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+}
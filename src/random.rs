@@ -0,0 +1,140 @@
+/// Size of the interpreter page the COSMAC VIP generator reads from.
+pub const VIP_PAGE_SIZE: usize = 0x100;
+
+/// Which generator `CXKK` draws from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RandomMode {
+    Xorshift,
+    CosmacVip,
+}
+
+impl RandomMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xorshift" => Some(RandomMode::Xorshift),
+            "vip" => Some(RandomMode::CosmacVip),
+            _ => None,
+        }
+    }
+}
+
+/// A small deterministic generator whose whole state can be saved and restored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Random {
+    mode: RandomMode,
+    seed: u64,
+    state: u64,
+    vip_pointer: u8,
+    vip_value: u8,
+    vip_page: Vec<u8>,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so the seed is scrambled first.
+        let state = splitmix64(seed) | 1;
+
+        Random {
+            mode: RandomMode::Xorshift,
+            seed,
+            state,
+            vip_pointer: seed as u8,
+            vip_value: (seed >> 8) as u8,
+            vip_page: vec![0; VIP_PAGE_SIZE],
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_mode(&mut self, mode: RandomMode) {
+        self.mode = mode;
+    }
+
+    /// Supplies the VIP interpreter code the COSMAC VIP mode reads, which is not part of the
+    /// emulated memory. Until then the page reads as zeros.
+    pub fn set_vip_page(&mut self, page: &[u8]) {
+        for (i, byte) in self.vip_page.iter_mut().enumerate() {
+            *byte = page.get(i).cloned().unwrap_or(0);
+        }
+    }
+
+    /// The next random byte.
+    pub fn next_byte(&mut self) -> u8 {
+        match self.mode {
+            RandomMode::Xorshift => {
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 7;
+                self.state ^= self.state << 17;
+                (self.state >> 32) as u8
+            }
+            // The VIP interpreter steps a pointer through a page of its own code and adds the
+            // byte found there to a running total.
+            RandomMode::CosmacVip => {
+                self.vip_pointer = self.vip_pointer.wrapping_add(1);
+                let byte = self.vip_page[self.vip_pointer as usize];
+                self.vip_value = self.vip_value.wrapping_add(byte).rotate_right(1);
+                self.vip_value
+            }
+        }
+    }
+
+    /// Called from the 60 Hz interrupt, which also stirs the VIP generator.
+    pub fn tick(&mut self) {
+        if self.mode == RandomMode::CosmacVip {
+            self.vip_value = self.vip_value.wrapping_add(1);
+        }
+    }
+
+    /// The state saved in save states, as `(xorshift state, VIP pointer, VIP total)`.
+    pub fn state(&self) -> (u64, u8, u8) {
+        (self.state, self.vip_pointer, self.vip_value)
+    }
+
+    pub fn set_state(&mut self, state: u64, vip_pointer: u8, vip_value: u8) {
+        self.state = if state == 0 { 1 } else { state };
+        self.vip_pointer = vip_pointer;
+        self.vip_value = vip_value;
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vip_mode_sums_the_interpreter_page() {
+        let page: Vec<u8> = (0..VIP_PAGE_SIZE).map(|i| i as u8).collect();
+        let mut random = Random::new(0);
+        random.set_mode(RandomMode::CosmacVip);
+        random.set_vip_page(&page);
+
+        assert_eq!(random.next_byte(), 0x80);
+        assert_eq!(random.next_byte(), 0x41);
+        assert_eq!(random.next_byte(), 0x22);
+
+        random.tick();
+        assert_eq!(random.next_byte(), 0x93);
+        assert_eq!(random.state().1, 4);
+    }
+
+    #[test]
+    fn vip_pointer_wraps_within_the_page() {
+        let mut page = vec![0; VIP_PAGE_SIZE];
+        page[0] = 0x10;
+        let mut random = Random::new(0xff);
+        random.set_mode(RandomMode::CosmacVip);
+        random.set_vip_page(&page);
+
+        assert_eq!(random.next_byte(), 0x08);
+        assert_eq!(random.state().1, 0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+
+use cpu::CPU;
+use error::EmulatorError;
+
+/// Default memory budget for the rewind history, in bytes.
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+/// A history of save states, newest last.
+///
+/// Only the newest state is kept whole. Every older one is stored as the XOR of itself
+/// with the state after it, run-length encoded, so frames that change little cost only
+/// a few bytes. Once the history outgrows its budget the oldest frames are dropped.
+pub struct Rewind {
+    budget: usize,
+    newest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+}
+
+impl Rewind {
+    pub fn new(budget: usize) -> Self {
+        Rewind { budget, newest: None, deltas: VecDeque::new(), used: 0 }
+    }
+
+    /// The number of frames that can be stepped back through.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Bytes currently held, which stays within the budget apart from the newest state.
+    pub fn memory_used(&self) -> usize {
+        self.used + self.newest.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Records the state the CPU is in now.
+    pub fn record(&mut self, cpu: &CPU) {
+        let state = cpu.save_state();
+
+        if let Some(previous) = self.newest.take() {
+            let delta = encode(&previous, &state);
+            self.used += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.newest = Some(state);
+
+        while self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.used -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Puts the CPU back one recorded frame, returning false when the history is exhausted.
+    pub fn step_back(&mut self, cpu: &mut CPU) -> Result<bool, EmulatorError> {
+        let (delta, newest) = match (self.deltas.pop_back(), self.newest.as_ref()) {
+            (Some(delta), Some(newest)) => (delta, newest),
+            _ => return Ok(false),
+        };
+        self.used -= delta.len();
+
+        let previous = decode(newest, &delta);
+        cpu.load_state(&previous)?;
+        self.newest = Some(previous);
+        Ok(true)
+    }
+
+    /// Forgets the history, for when the CPU jumps to an unrelated state such as a loaded save.
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.used = 0;
+    }
+}
+
+/// Encodes `older` relative to `newer`: its length, then alternating runs of unchanged
+/// bytes (a count) and changed bytes (a count followed by the XORed bytes).
+fn encode(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = (0..older.len())
+        .map(|i| older[i] ^ newer.get(i).cloned().unwrap_or(0))
+        .collect();
+
+    let mut delta = Vec::new();
+    write_count(&mut delta, older.len());
+
+    let mut i = 0;
+    while i < xor.len() {
+        let unchanged = xor[i..].iter().take_while(|&&byte| byte == 0).count();
+        i += unchanged;
+
+        let changed = xor[i..].iter().take_while(|&&byte| byte != 0).count();
+        write_count(&mut delta, unchanged);
+        write_count(&mut delta, changed);
+        delta.extend_from_slice(&xor[i..i + changed]);
+        i += changed;
+    }
+
+    delta
+}
+
+fn decode(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut position = 0;
+    let len = read_count(delta, &mut position);
+
+    let mut older: Vec<u8> = (0..len).map(|i| newer.get(i).cloned().unwrap_or(0)).collect();
+
+    let mut i = 0;
+    while position < delta.len() {
+        i += read_count(delta, &mut position);
+        let changed = read_count(delta, &mut position);
+
+        for byte in &mut older[i..i + changed] {
+            *byte ^= delta[position];
+            position += 1;
+        }
+        i += changed;
+    }
+
+    older
+}
+
+/// Writes a count seven bits at a time, low bits first.
+fn write_count(bytes: &mut Vec<u8>, mut count: usize) {
+    while count >= 0x80 {
+        bytes.push((count & 0x7f) as u8 | 0x80);
+        count >>= 7;
+    }
+    bytes.push(count as u8);
+}
+
+fn read_count(bytes: &[u8], position: &mut usize) -> usize {
+    let mut count = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+        count |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return count;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_round_trip_across_varint_boundaries() {
+        let counts = [0, 1, 127, 128, 129, 16383, 16384, 16385, 2_097_151, 2_097_152];
+        let lengths = [1, 1, 1, 2, 2, 2, 3, 3, 3, 4];
+
+        for (&count, &length) in counts.iter().zip(lengths.iter()) {
+            let mut bytes = Vec::new();
+            write_count(&mut bytes, count);
+            assert_eq!(bytes.len(), length, "{}", count);
+
+            let mut position = 0;
+            assert_eq!(read_count(&bytes, &mut position), count);
+            assert_eq!(position, bytes.len());
+        }
+    }
+
+    #[test]
+    fn deltas_round_trip_across_run_lengths() {
+        for &run in &[1, 2, 127, 128, 16383, 16384, 20000] {
+            let newer: Vec<u8> = (0..3 * run + 7).map(|i| (i * 7) as u8).collect();
+
+            // Alternating unchanged and changed runs of the given length.
+            let mut older = newer.clone();
+            for (i, byte) in older.iter_mut().enumerate() {
+                if (i / run) % 2 == 1 {
+                    *byte = !*byte;
+                }
+            }
+
+            let delta = encode(&older, &newer);
+            assert_eq!(decode(&newer, &delta), older, "run of {}", run);
+        }
+    }
+
+    #[test]
+    fn deltas_round_trip_between_different_lengths() {
+        let short = vec![1, 2, 3];
+        let long = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+        assert_eq!(decode(&long, &encode(&short, &long)), short);
+        assert_eq!(decode(&short, &encode(&long, &short)), long);
+        assert_eq!(decode(&long, &encode(&[], &long)), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn identical_states_cost_a_few_bytes() {
+        let state = vec![0x5a; 5000];
+        let delta = encode(&state, &state);
+        assert!(delta.len() <= 6, "{} bytes", delta.len());
+        assert_eq!(decode(&state, &delta), state);
+    }
+}
@@ -0,0 +1,188 @@
+use std::fs;
+
+use cpu::CPU;
+use error::EmulatorError;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+
+/// Bumped whenever fields are added; older versions stay loadable.
+pub const VERSION: u16 = 1;
+
+pub const SLOT_COUNT: usize = 4;
+
+/// Builds a save state: the header followed by little-endian fields.
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter::with_header(MAGIC, VERSION)
+    }
+
+    /// Starts a file of another kind that shares the layout, such as a movie.
+    pub fn with_header(magic: &[u8; 4], version: u16) -> Self {
+        let mut writer = StateWriter { bytes: Vec::new() };
+        writer.bytes.extend_from_slice(magic);
+        writer.u16(version);
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    /// Writes a length-prefixed byte slice.
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        StateWriter::new()
+    }
+}
+
+/// Reads back what a `StateWriter` produced, checking the header first.
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+    version: u16,
+    error: fn(String) -> EmulatorError,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, EmulatorError> {
+        StateReader::with_header(bytes, MAGIC, VERSION, EmulatorError::InvalidSaveState)
+    }
+
+    /// Reads a file written by `StateWriter::with_header`, accepting versions up to
+    /// `max_version` and reporting problems through `error`.
+    pub fn with_header(bytes: &'a [u8], magic: &[u8; 4], max_version: u16,
+                       error: fn(String) -> EmulatorError) -> Result<Self, EmulatorError> {
+        if bytes.len() < magic.len() || &bytes[..magic.len()] != magic {
+            return Err(error("wrong file type".to_string()));
+        }
+
+        let mut reader = StateReader { bytes: &bytes[magic.len()..], version: 0, error };
+        let version = reader.u16()?;
+        if version == 0 || version > max_version {
+            return Err(error(format!("unsupported version {}", version)));
+        }
+
+        reader.version = version;
+        Ok(reader)
+    }
+
+    /// The version the state was written with, for skipping fields it predates.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Whether every byte has been read.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], EmulatorError> {
+        if self.bytes.len() < count {
+            return Err((self.error)("truncated".to_string()));
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, EmulatorError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, EmulatorError> {
+        let mut buffer = [0; 2];
+        buffer.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, EmulatorError> {
+        let mut buffer = [0; 4];
+        buffer.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, EmulatorError> {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, EmulatorError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], EmulatorError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+pub fn invalid(reason: &str) -> EmulatorError {
+    EmulatorError::InvalidSaveState(reason.to_string())
+}
+
+/// Numbered save state files kept next to the ROM.
+pub struct SaveSlots {
+    rom_path: String,
+    slot: usize,
+}
+
+impl SaveSlots {
+    pub fn new(rom_path: &str) -> Self {
+        SaveSlots { rom_path: rom_path.to_string(), slot: 1 }
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// Selects slot 1 to `SLOT_COUNT`; anything else is ignored.
+    pub fn select(&mut self, slot: usize) {
+        if (1..=SLOT_COUNT).contains(&slot) {
+            self.slot = slot;
+        }
+    }
+
+    pub fn path(&self) -> String {
+        format!("{}.state{}", self.rom_path, self.slot)
+    }
+
+    pub fn save(&self, cpu: &CPU) -> Result<(), EmulatorError> {
+        fs::write(self.path(), cpu.save_state()).map_err(|e| EmulatorError::Io(e.to_string()))
+    }
+
+    pub fn load(&self, cpu: &mut CPU) -> Result<(), EmulatorError> {
+        let state = fs::read(self.path()).map_err(|e| EmulatorError::Io(e.to_string()))?;
+        cpu.load_state(&state)
+    }
+}
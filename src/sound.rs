@@ -1,6 +1,9 @@
 use sdl2;
 use sdl2::audio::{AudioDevice, AudioCallback, AudioSpecDesired};
 
+use cpu::AudioPattern;
+use frontend::AudioSink;
+
 pub struct Sound {
     device: AudioDevice<SquareWave>,
 }
@@ -22,37 +25,62 @@ impl Sound {
 
                 // initialize the audio callback
                 SquareWave {
+                    freq: spec.freq as f32,
                     phase_inc: 240.0 / spec.freq as f32,
                     phase: 0.0,
                     volume: 0.25,
+                    pattern: None,
                 }
             })
             .unwrap();
 
         Sound { device }
     }
+}
 
-    pub fn start_beep(&self) {
+impl AudioSink for Sound {
+    fn start_beep(&mut self) {
         self.device.resume();
     }
-    pub fn stop_beep(&self) {
+    fn stop_beep(&mut self) {
         self.device.pause();
     }
+
+    fn set_pattern(&mut self, pattern: &AudioPattern) {
+        let mut wave = self.device.lock();
+        wave.phase_inc = pattern.sample_rate() / 128.0 / wave.freq;
+        wave.pattern = Some(pattern.buffer);
+    }
 }
 
 struct SquareWave {
+    freq: f32,
     phase_inc: f32,
     phase: f32,
     volume: f32,
+    pattern: Option<[u8; 16]>,
+}
+
+impl SquareWave {
+    /// The sample at the current phase: a plain square wave, or a bit of the XO-CHIP pattern.
+    fn level(&self) -> f32 {
+        match self.pattern {
+            Some(ref pattern) => {
+                let bit = (self.phase * 128.0) as usize;
+                if (pattern[bit / 8] >> (7 - bit % 8)) & 1 == 1 { 1.0 } else { -1.0 }
+            }
+            None => if self.phase < 0.5 { 1.0 } else { -1.0 },
+        }
+    }
 }
 
 impl AudioCallback for SquareWave {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
+        // Generate a square wave, or play back the pattern buffer
         for x in out.iter_mut() {
-            *x = self.volume * if self.phase < 0.5 { 1.0 } else { -1.0 };
+            *x = self.volume * self.level();
             self.phase = (self.phase + self.phase_inc) % 1.0;
         }
     }
@@ -0,0 +1,59 @@
+/// The part of the window the framebuffer is drawn into, in drawable pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// The largest area with the framebuffer's aspect ratio that fits the output, centred.
+    ///
+    /// With `integer_scale` each CHIP-8 pixel covers a whole number of host pixels, unless the
+    /// output is too small for even one.
+    pub fn letterbox(output: (u32, u32), framebuffer: (usize, usize), integer_scale: bool) -> Self {
+        let (output_width, output_height) = output;
+        let (frame_width, frame_height) = (framebuffer.0 as f64, framebuffer.1 as f64);
+
+        let mut scale = (output_width as f64 / frame_width).min(output_height as f64 / frame_height);
+        if integer_scale && scale >= 1.0 {
+            scale = scale.floor();
+        }
+
+        let width = ((frame_width * scale).round() as u32).min(output_width);
+        let height = ((frame_height * scale).round() as u32).min(output_height);
+        Viewport {
+            x: (output_width - width) / 2,
+            y: (output_height - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_window_gets_bars_at_the_sides() {
+        let viewport = Viewport::letterbox((1000, 400), (64, 32), false);
+        assert_eq!(viewport, Viewport { x: 100, y: 0, width: 800, height: 400 });
+    }
+
+    #[test]
+    fn tall_window_gets_bars_above_and_below() {
+        let viewport = Viewport::letterbox((640, 480), (128, 64), false);
+        assert_eq!(viewport, Viewport { x: 0, y: 80, width: 640, height: 320 });
+    }
+
+    #[test]
+    fn integer_scale_rounds_down() {
+        let viewport = Viewport::letterbox((700, 400), (64, 32), true);
+        assert_eq!(viewport, Viewport { x: 30, y: 40, width: 640, height: 320 });
+
+        let fractional = Viewport::letterbox((700, 400), (64, 32), false);
+        assert_eq!(fractional, Viewport { x: 0, y: 25, width: 700, height: 350 });
+    }
+}
@@ -0,0 +1,160 @@
+//! Runs test programs through the headless core under every quirk preset and compares the final
+//! framebuffer with the golden image in `tests/golden`.
+//!
+//! The regression programs in `roms` are always run. The Timendus CHIP-8 test suite cannot be
+//! bundled under its license, so its tests are ignored by default: download it into
+//! `tests/suite` with `tests/fetch-test-suite.sh`, then run `cargo test -- --ignored`.
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the golden images after an intended change, and check the
+//! new images by eye before committing them.
+
+extern crate chip8;
+
+mod roms;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use chip8::cpu::CPU;
+use chip8::headless::{self, Limit, Script};
+use chip8::memory::{self, RAM};
+use chip8::quirks::Quirks;
+
+const PRESETS: &[&str] = &["vip", "chip48", "schip", "xochip"];
+
+/// Long enough for every program to finish, even with the VIP's display wait.
+const FRAMES: usize = 120;
+
+/// The test suite ROMs measure the display wait over several seconds.
+const SUITE_FRAMES: usize = 600;
+
+/// The suite ROMs read this byte to pick their test without showing a menu.
+const SUITE_SELECTION_ADDRESS: usize = 0x1FF;
+
+struct Run<'a> {
+    rom: &'a [u8],
+    script: &'a str,
+    frames: usize,
+    /// Stored at `SUITE_SELECTION_ADDRESS` before the run, for each preset.
+    selection: fn(&str) -> Option<u8>,
+}
+
+fn run(test: &Run, preset: &str) -> String {
+    let quirks = Quirks::from_name(preset).expect("known preset");
+    let mut ram = RAM::with_size(memory::size_for_preset(preset));
+    ram.memory[memory::PROGRAM_START..memory::PROGRAM_START + test.rom.len()].copy_from_slice(test.rom);
+    if let Some(selection) = (test.selection)(preset) {
+        ram.memory[SUITE_SELECTION_ADDRESS] = selection;
+    }
+
+    let mut cpu = CPU::new(ram.memory, quirks, 0);
+    let mut script = Script::parse(test.script).expect("valid input script");
+    headless::run(&mut cpu, &mut script, Limit::Frames(test.frames)).expect("program runs");
+
+    headless::pbm(cpu.video_memory())
+}
+
+fn check(name: &str, rom: &[u8], script: &str) {
+    check_run(name, &Run { rom, script, frames: FRAMES, selection: |_| None });
+}
+
+fn check_run(name: &str, test: &Run) {
+    let golden_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    for preset in PRESETS {
+        let image = run(test, preset);
+        let path = golden_dir.join(format!("{}-{}.pbm", name, preset));
+
+        if update {
+            fs::write(&path, &image).expect("golden image written");
+            continue;
+        }
+
+        let golden = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("{}: {}, run with UPDATE_GOLDEN=1", path.display(), err));
+        if image != golden {
+            failures.push(format!("{} under {}:\n{}", name, preset, image));
+        }
+    }
+
+    assert!(failures.is_empty(), "framebuffer differs from the golden image\n{}", failures.join("\n"));
+}
+
+#[test]
+fn logo() {
+    check("logo", roms::LOGO, "");
+}
+
+#[test]
+fn opcodes() {
+    check("opcodes", roms::OPCODES, "");
+}
+
+#[test]
+fn flags() {
+    check("flags", roms::FLAGS, "");
+}
+
+#[test]
+fn quirks() {
+    check("quirks", roms::QUIRKS, "");
+}
+
+#[test]
+fn keypad() {
+    check("keypad", roms::KEYPAD, "0 -\n10 5\n15 -\n30 A\n40 -\n");
+}
+
+/// Runs a ROM of the Timendus test suite, which must have been fetched.
+fn check_suite(name: &str, file: &str, script: &str, selection: fn(&str) -> Option<u8>) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("suite").join(file);
+    let rom = fs::read(&path)
+        .unwrap_or_else(|err| panic!("{}: {}, run tests/fetch-test-suite.sh", path.display(), err));
+
+    check_run(name, &Run { rom: &rom, script, frames: SUITE_FRAMES, selection });
+}
+
+#[test]
+#[ignore = "needs tests/fetch-test-suite.sh"]
+fn suite_chip8_logo() {
+    check_suite("suite-chip8-logo", "1-chip8-logo.ch8", "", |_| None);
+}
+
+#[test]
+#[ignore = "needs tests/fetch-test-suite.sh"]
+fn suite_ibm_logo() {
+    check_suite("suite-ibm-logo", "2-ibm-logo.ch8", "", |_| None);
+}
+
+#[test]
+#[ignore = "needs tests/fetch-test-suite.sh"]
+fn suite_corax_plus() {
+    check_suite("suite-corax-plus", "3-corax+.ch8", "", |_| None);
+}
+
+#[test]
+#[ignore = "needs tests/fetch-test-suite.sh"]
+fn suite_flags() {
+    check_suite("suite-flags", "4-flags.ch8", "", |_| None);
+}
+
+#[test]
+#[ignore = "needs tests/fetch-test-suite.sh"]
+fn suite_quirks() {
+    // The ROM's own platform choices; CHIP-48 has none, so it is tested as SUPER-CHIP.
+    check_suite("suite-quirks", "5-quirks.ch8", "", |preset| match preset {
+        "vip" => Some(1),
+        "chip48" | "schip" => Some(2),
+        _ => Some(3),
+    });
+}
+
+#[test]
+#[ignore = "needs tests/fetch-test-suite.sh"]
+fn suite_keypad() {
+    // The FX0A test, answered by pressing and releasing key 5.
+    check_suite("suite-keypad", "6-keypad.ch8", "0 -\n60 5\n70 -\n", |_| Some(3));
+}
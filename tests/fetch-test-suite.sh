@@ -0,0 +1,20 @@
+#!/bin/sh
+# Downloads the Timendus CHIP-8 test suite (GPL-3.0) into tests/suite for tests/conformance.rs.
+# The ROMs are not committed, as their license differs from this crate's.
+#
+# Set REF to a tag or commit of https://github.com/Timendus/chip8-test-suite to pin the version.
+
+set -e
+
+REF=${REF:-main}
+BASE="https://raw.githubusercontent.com/Timendus/chip8-test-suite/$REF"
+DIR="$(dirname "$0")/suite"
+
+mkdir -p "$DIR"
+curl -fsSL -o "$DIR/LICENSE" "$BASE/LICENSE"
+for rom in 1-chip8-logo.ch8 2-ibm-logo.ch8 3-corax+.ch8 4-flags.ch8 5-quirks.ch8 6-keypad.ch8; do
+    curl -fsSL -o "$DIR/$rom" "$BASE/bin/$rom"
+done
+
+echo "Fetched the test suite at $REF into $DIR."
+echo "Run the suite with: cargo test --test conformance -- --ignored"
@@ -0,0 +1,395 @@
+//! Hand-assembled regression programs, loaded at 0x200. Each line gives the address and mnemonic.
+//!
+//! These are written for this emulator and only cover the same ground as the well-known test
+//! ROMs; they are not those ROMs. The real ones are run by `suite_*` in `conformance.rs`.
+
+/// Draws three 15-row sprites, like the IBM logo ROM: `00E0`, `6XKK`, `7XKK`, `ANNN`, `DXYN`, `1NNN`.
+pub const LOGO: &[u8] = &[
+    0x00, 0xE0,  // 200  CLS
+    0x60, 0x0C,  // 202  LD V0, 12
+    0x61, 0x08,  // 204  LD V1, 8
+    0xA2, 0x18,  // 206  LD I, c
+    0xD0, 0x1F,  // 208  DRW V0, V1, 15
+    0x70, 0x0C,  // 20A  ADD V0, 12
+    0xA2, 0x27,  // 20C  LD I, eight
+    0xD0, 0x1F,  // 20E  DRW V0, V1, 15
+    0x70, 0x0C,  // 210  ADD V0, 12
+    0xA2, 0x36,  // 212  LD I, heart
+    0xD0, 0x1F,  // 214  DRW V0, V1, 15
+    // done:
+    0x12, 0x16,  // 216  JP done
+    // c:
+    0x3E, 0x7F, 0xE3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xE3, 0x7F, 0x3E,  // 218  C
+    // eight:
+    0x3E, 0x7F, 0xE3, 0xC1, 0xC1, 0xE3, 0x7F, 0x3E, 0x7F, 0xE3, 0xC1, 0xC1, 0xE3, 0x7F, 0x3E,  // 227  8
+    // heart:
+    0x00, 0x66, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7E, 0x7E, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x00,  // 236  heart
+];
+
+/// Runs each basic opcode once and draws a tick or a cross for it, in the manner of corax+.
+pub const OPCODES: &[u8] = &[
+    0x00, 0xE0,  // 200  CLS
+    0x68, 0x00,  // 202  LD V8, 0
+    0x69, 0x00,  // 204  LD V9, 0
+    0x6A, 0x00,  // 206  LD VA, 0x00
+    0x6B, 0x05,  // 208  LD VB, 0x05
+    0x3B, 0x05,  // 20A  SE VB, 0x05
+    0x7A, 0x01,  // 20C  ADD VA, 1
+    0xA3, 0x13,  // 20E  LD I, cross
+    0x4A, 0x00,  // 210  SNE VA, 0x00
+    0xA3, 0x0E,  // 212  LD I, tick
+    0x23, 0x00,  // 214  CALL mark   ; 3XKK
+    0x6A, 0x00,  // 216  LD VA, 0x00
+    0x6B, 0x05,  // 218  LD VB, 0x05
+    0x4B, 0x06,  // 21A  SNE VB, 0x06
+    0x7A, 0x01,  // 21C  ADD VA, 1
+    0xA3, 0x13,  // 21E  LD I, cross
+    0x4A, 0x00,  // 220  SNE VA, 0x00
+    0xA3, 0x0E,  // 222  LD I, tick
+    0x23, 0x00,  // 224  CALL mark   ; 4XKK
+    0x6A, 0x00,  // 226  LD VA, 0x00
+    0x6B, 0x05,  // 228  LD VB, 0x05
+    0x6C, 0x05,  // 22A  LD VC, 0x05
+    0x5B, 0xC0,  // 22C  SE VB, VC
+    0x7A, 0x01,  // 22E  ADD VA, 1
+    0xA3, 0x13,  // 230  LD I, cross
+    0x4A, 0x00,  // 232  SNE VA, 0x00
+    0xA3, 0x0E,  // 234  LD I, tick
+    0x23, 0x00,  // 236  CALL mark   ; 5XY0
+    0x6A, 0x00,  // 238  LD VA, 0x00
+    0x6B, 0x05,  // 23A  LD VB, 0x05
+    0x6C, 0x06,  // 23C  LD VC, 0x06
+    0x9B, 0xC0,  // 23E  SNE VB, VC
+    0x7A, 0x01,  // 240  ADD VA, 1
+    0xA3, 0x13,  // 242  LD I, cross
+    0x4A, 0x00,  // 244  SNE VA, 0x00
+    0xA3, 0x0E,  // 246  LD I, tick
+    0x23, 0x00,  // 248  CALL mark   ; 9XY0
+    0x6A, 0xFF,  // 24A  LD VA, 0xFF
+    0x7A, 0x02,  // 24C  ADD VA, 2
+    0xA3, 0x13,  // 24E  LD I, cross
+    0x4A, 0x01,  // 250  SNE VA, 0x01
+    0xA3, 0x0E,  // 252  LD I, tick
+    0x23, 0x00,  // 254  CALL mark   ; 7XKK
+    0x6B, 0x42,  // 256  LD VB, 0x42
+    0x8A, 0xB0,  // 258  LD VA, VB
+    0xA3, 0x13,  // 25A  LD I, cross
+    0x4A, 0x42,  // 25C  SNE VA, 0x42
+    0xA3, 0x0E,  // 25E  LD I, tick
+    0x23, 0x00,  // 260  CALL mark   ; 8XY0
+    0x6A, 0x0F,  // 262  LD VA, 0x0F
+    0x6B, 0xF0,  // 264  LD VB, 0xF0
+    0x8A, 0xB1,  // 266  OR VA, VB
+    0xA3, 0x13,  // 268  LD I, cross
+    0x4A, 0xFF,  // 26A  SNE VA, 0xff
+    0xA3, 0x0E,  // 26C  LD I, tick
+    0x23, 0x00,  // 26E  CALL mark   ; 8XY1
+    0x6A, 0x3C,  // 270  LD VA, 0x3C
+    0x6B, 0x0F,  // 272  LD VB, 0x0F
+    0x8A, 0xB2,  // 274  AND VA, VB
+    0xA3, 0x13,  // 276  LD I, cross
+    0x4A, 0x0C,  // 278  SNE VA, 0x0c
+    0xA3, 0x0E,  // 27A  LD I, tick
+    0x23, 0x00,  // 27C  CALL mark   ; 8XY2
+    0x6A, 0x3C,  // 27E  LD VA, 0x3C
+    0x6B, 0x0F,  // 280  LD VB, 0x0F
+    0x8A, 0xB3,  // 282  XOR VA, VB
+    0xA3, 0x13,  // 284  LD I, cross
+    0x4A, 0x33,  // 286  SNE VA, 0x33
+    0xA3, 0x0E,  // 288  LD I, tick
+    0x23, 0x00,  // 28A  CALL mark   ; 8XY3
+    0x6A, 0xF0,  // 28C  LD VA, 0xF0
+    0x6B, 0x20,  // 28E  LD VB, 0x20
+    0x8A, 0xB4,  // 290  ADD VA, VB
+    0xA3, 0x13,  // 292  LD I, cross
+    0x4A, 0x10,  // 294  SNE VA, 0x10
+    0xA3, 0x0E,  // 296  LD I, tick
+    0x23, 0x00,  // 298  CALL mark   ; 8XY4
+    0x6A, 0x20,  // 29A  LD VA, 0x20
+    0x6B, 0x30,  // 29C  LD VB, 0x30
+    0x8A, 0xB5,  // 29E  SUB VA, VB
+    0xA3, 0x13,  // 2A0  LD I, cross
+    0x4A, 0xF0,  // 2A2  SNE VA, 0xf0
+    0xA3, 0x0E,  // 2A4  LD I, tick
+    0x23, 0x00,  // 2A6  CALL mark   ; 8XY5
+    0x6A, 0x20,  // 2A8  LD VA, 0x20
+    0x6B, 0x30,  // 2AA  LD VB, 0x30
+    0x8A, 0xB7,  // 2AC  SUBN VA, VB
+    0xA3, 0x13,  // 2AE  LD I, cross
+    0x4A, 0x10,  // 2B0  SNE VA, 0x10
+    0xA3, 0x0E,  // 2B2  LD I, tick
+    0x23, 0x00,  // 2B4  CALL mark   ; 8XY7
+    0x6A, 0xEA,  // 2B6  LD VA, 234
+    0xA3, 0x28,  // 2B8  LD I, scratch
+    0xFA, 0x33,  // 2BA  LD B, VA
+    0xF2, 0x65,  // 2BC  LD V2, [I]
+    0x8A, 0x20,  // 2BE  LD VA, V2
+    0xA3, 0x13,  // 2C0  LD I, cross
+    0x4A, 0x04,  // 2C2  SNE VA, 0x04
+    0xA3, 0x0E,  // 2C4  LD I, tick
+    0x23, 0x00,  // 2C6  CALL mark   ; FX33
+    0x60, 0x05,  // 2C8  LD V0, 0x05
+    0xA3, 0x18,  // 2CA  LD I, scratch - 0x10
+    0x6A, 0x10,  // 2CC  LD VA, 0x10
+    0xFA, 0x1E,  // 2CE  ADD I, VA
+    0xF0, 0x55,  // 2D0  LD [I], V0
+    0xA3, 0x28,  // 2D2  LD I, scratch
+    0x60, 0x00,  // 2D4  LD V0, 0
+    0xF0, 0x65,  // 2D6  LD V0, [I]
+    0x8A, 0x00,  // 2D8  LD VA, V0
+    0xA3, 0x13,  // 2DA  LD I, cross
+    0x4A, 0x05,  // 2DC  SNE VA, 0x05
+    0xA3, 0x0E,  // 2DE  LD I, tick
+    0x23, 0x00,  // 2E0  CALL mark   ; FX1E FX55 FX65
+    0x6A, 0x05,  // 2E2  LD VA, 0x05
+    0xFA, 0x15,  // 2E4  LD DT, VA
+    0xFA, 0x07,  // 2E6  LD VA, DT
+    0x8A, 0xA6,  // 2E8  SHR VA, VA  ; 4 or 5 become 2, in case the timer ticked in between
+    0xA3, 0x13,  // 2EA  LD I, cross
+    0x4A, 0x02,  // 2EC  SNE VA, 0x02
+    0xA3, 0x0E,  // 2EE  LD I, tick
+    0x23, 0x00,  // 2F0  CALL mark   ; FX15 FX07
+    0x6A, 0x55,  // 2F2  LD VA, 0x55
+    0xCA, 0x00,  // 2F4  RND VA, 0x00
+    0xA3, 0x13,  // 2F6  LD I, cross
+    0x4A, 0x00,  // 2F8  SNE VA, 0x00
+    0xA3, 0x0E,  // 2FA  LD I, tick
+    0x23, 0x00,  // 2FC  CALL mark   ; CXKK
+    // done:
+    0x12, 0xFE,  // 2FE  JP done
+    // mark:
+    0xD8, 0x95,  // 300  DRW V8, V9, 5
+    0x78, 0x06,  // 302  ADD V8, 6
+    0x38, 0x3C,  // 304  SE V8, 60
+    0x00, 0xEE,  // 306  RET
+    0x68, 0x00,  // 308  LD V8, 0
+    0x79, 0x06,  // 30A  ADD V9, 6
+    0x00, 0xEE,  // 30C  RET
+    // tick:
+    0x08, 0x10, 0xA0, 0x40, 0x00,  // 30E  tick sprite
+    // cross:
+    0x88, 0x50, 0x20, 0x50, 0x88,  // 313  cross sprite
+    // below_scratch:
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,  // 318  scratch - 0x10
+    // scratch:
+    0x00, 0x00, 0x00, 0x00,  // 328  scratch
+];
+
+/// Checks the carry and borrow flags, including when VF is itself an operand, with ticks and crosses.
+pub const FLAGS: &[u8] = &[
+    0x00, 0xE0,  // 200  CLS
+    0x68, 0x00,  // 202  LD V8, 0
+    0x69, 0x00,  // 204  LD V9, 0
+    0x6A, 0xFF,  // 206  LD VA, 0xFF
+    0x6B, 0x01,  // 208  LD VB, 0x01
+    0x8A, 0xB4,  // 20A  ADD VA, VB
+    0x8A, 0xF0,  // 20C  LD VA, VF
+    0xA2, 0xF7,  // 20E  LD I, cross
+    0x4A, 0x01,  // 210  SNE VA, 0x01
+    0xA2, 0xF2,  // 212  LD I, tick
+    0x22, 0xE4,  // 214  CALL mark   ; 8XY4 carry
+    0x6A, 0x01,  // 216  LD VA, 0x01
+    0x6B, 0x01,  // 218  LD VB, 0x01
+    0x8A, 0xB4,  // 21A  ADD VA, VB
+    0x8A, 0xF0,  // 21C  LD VA, VF
+    0xA2, 0xF7,  // 21E  LD I, cross
+    0x4A, 0x00,  // 220  SNE VA, 0x00
+    0xA2, 0xF2,  // 222  LD I, tick
+    0x22, 0xE4,  // 224  CALL mark   ; 8XY4 no carry
+    0x6A, 0x05,  // 226  LD VA, 0x05
+    0x6B, 0x05,  // 228  LD VB, 0x05
+    0x8A, 0xB5,  // 22A  SUB VA, VB
+    0x8A, 0xF0,  // 22C  LD VA, VF
+    0xA2, 0xF7,  // 22E  LD I, cross
+    0x4A, 0x01,  // 230  SNE VA, 0x01
+    0xA2, 0xF2,  // 232  LD I, tick
+    0x22, 0xE4,  // 234  CALL mark   ; 8XY5 equal, no borrow
+    0x6A, 0x04,  // 236  LD VA, 0x04
+    0x6B, 0x05,  // 238  LD VB, 0x05
+    0x8A, 0xB5,  // 23A  SUB VA, VB
+    0x8A, 0xF0,  // 23C  LD VA, VF
+    0xA2, 0xF7,  // 23E  LD I, cross
+    0x4A, 0x00,  // 240  SNE VA, 0x00
+    0xA2, 0xF2,  // 242  LD I, tick
+    0x22, 0xE4,  // 244  CALL mark   ; 8XY5 borrow
+    0x6A, 0x05,  // 246  LD VA, 0x05
+    0x6B, 0x05,  // 248  LD VB, 0x05
+    0x8A, 0xB7,  // 24A  SUBN VA, VB
+    0x8A, 0xF0,  // 24C  LD VA, VF
+    0xA2, 0xF7,  // 24E  LD I, cross
+    0x4A, 0x01,  // 250  SNE VA, 0x01
+    0xA2, 0xF2,  // 252  LD I, tick
+    0x22, 0xE4,  // 254  CALL mark   ; 8XY7 equal, no borrow
+    0x6A, 0x03,  // 256  LD VA, 0x03
+    0x6B, 0x03,  // 258  LD VB, 0x03
+    0x8A, 0xB6,  // 25A  SHR VA, VB
+    0x8A, 0xF0,  // 25C  LD VA, VF
+    0xA2, 0xF7,  // 25E  LD I, cross
+    0x4A, 0x01,  // 260  SNE VA, 0x01
+    0xA2, 0xF2,  // 262  LD I, tick
+    0x22, 0xE4,  // 264  CALL mark   ; 8XY6 flag
+    0x6A, 0x81,  // 266  LD VA, 0x81
+    0x6B, 0x81,  // 268  LD VB, 0x81
+    0x8A, 0xBE,  // 26A  SHL VA, VB
+    0x8A, 0xF0,  // 26C  LD VA, VF
+    0xA2, 0xF7,  // 26E  LD I, cross
+    0x4A, 0x01,  // 270  SNE VA, 0x01
+    0xA2, 0xF2,  // 272  LD I, tick
+    0x22, 0xE4,  // 274  CALL mark   ; 8XYE flag
+    0x6F, 0x10,  // 276  LD VF, 0x10
+    0x6B, 0x10,  // 278  LD VB, 0x10
+    0x8F, 0xB4,  // 27A  ADD VF, VB
+    0x8A, 0xF0,  // 27C  LD VA, VF
+    0xA2, 0xF7,  // 27E  LD I, cross
+    0x4A, 0x00,  // 280  SNE VA, 0x00
+    0xA2, 0xF2,  // 282  LD I, tick
+    0x22, 0xE4,  // 284  CALL mark   ; 8XY4 into VF
+    0x6F, 0x10,  // 286  LD VF, 0x10
+    0x6B, 0x05,  // 288  LD VB, 0x05
+    0x8F, 0xB5,  // 28A  SUB VF, VB
+    0x8A, 0xF0,  // 28C  LD VA, VF
+    0xA2, 0xF7,  // 28E  LD I, cross
+    0x4A, 0x01,  // 290  SNE VA, 0x01
+    0xA2, 0xF2,  // 292  LD I, tick
+    0x22, 0xE4,  // 294  CALL mark   ; 8XY5 into VF
+    0x6F, 0x05,  // 296  LD VF, 0x05
+    0x6B, 0x05,  // 298  LD VB, 0x05
+    0x8F, 0xB6,  // 29A  SHR VF, VB
+    0x8A, 0xF0,  // 29C  LD VA, VF
+    0xA2, 0xF7,  // 29E  LD I, cross
+    0x4A, 0x01,  // 2A0  SNE VA, 0x01
+    0xA2, 0xF2,  // 2A2  LD I, tick
+    0x22, 0xE4,  // 2A4  CALL mark   ; 8XY6 into VF
+    0x6F, 0x02,  // 2A6  LD VF, 0x02
+    0x6B, 0x05,  // 2A8  LD VB, 0x05
+    0x8F, 0xB7,  // 2AA  SUBN VF, VB
+    0x8A, 0xF0,  // 2AC  LD VA, VF
+    0xA2, 0xF7,  // 2AE  LD I, cross
+    0x4A, 0x01,  // 2B0  SNE VA, 0x01
+    0xA2, 0xF2,  // 2B2  LD I, tick
+    0x22, 0xE4,  // 2B4  CALL mark   ; 8XY7 into VF
+    0x6F, 0xC0,  // 2B6  LD VF, 0xC0
+    0x6B, 0xC0,  // 2B8  LD VB, 0xC0
+    0x8F, 0xBE,  // 2BA  SHL VF, VB
+    0x8A, 0xF0,  // 2BC  LD VA, VF
+    0xA2, 0xF7,  // 2BE  LD I, cross
+    0x4A, 0x01,  // 2C0  SNE VA, 0x01
+    0xA2, 0xF2,  // 2C2  LD I, tick
+    0x22, 0xE4,  // 2C4  CALL mark   ; 8XYE into VF
+    0x6F, 0x01,  // 2C6  LD VF, 0x01
+    0x6A, 0xFF,  // 2C8  LD VA, 0xFF
+    0x8A, 0xF4,  // 2CA  ADD VA, VF
+    0xA2, 0xF7,  // 2CC  LD I, cross
+    0x4A, 0x00,  // 2CE  SNE VA, 0x00
+    0xA2, 0xF2,  // 2D0  LD I, tick
+    0x22, 0xE4,  // 2D2  CALL mark   ; 8XY4 from VF
+    0x6F, 0x05,  // 2D4  LD VF, 0x05
+    0x6A, 0x03,  // 2D6  LD VA, 0x03
+    0x8A, 0xF5,  // 2D8  SUB VA, VF
+    0xA2, 0xF7,  // 2DA  LD I, cross
+    0x4A, 0xFE,  // 2DC  SNE VA, 0xfe
+    0xA2, 0xF2,  // 2DE  LD I, tick
+    0x22, 0xE4,  // 2E0  CALL mark   ; 8XY5 from VF
+    // done:
+    0x12, 0xE2,  // 2E2  JP done
+    // mark:
+    0xD8, 0x95,  // 2E4  DRW V8, V9, 5
+    0x78, 0x06,  // 2E6  ADD V8, 6
+    0x38, 0x3C,  // 2E8  SE V8, 60
+    0x00, 0xEE,  // 2EA  RET
+    0x68, 0x00,  // 2EC  LD V8, 0
+    0x79, 0x06,  // 2EE  ADD V9, 6
+    0x00, 0xEE,  // 2F0  RET
+    // tick:
+    0x08, 0x10, 0xA0, 0x40, 0x00,  // 2F2  tick sprite
+    // cross:
+    0x88, 0x50, 0x20, 0x50, 0x88,  // 2F7  cross sprite
+];
+
+/// Draws one digit per quirk probe, so the image differs between presets:
+/// logic VF reset, shift source, FX55 increment, BNNN register and sprite clipping.
+pub const QUIRKS: &[u8] = &[
+    0x00, 0xE0,  // 200  CLS
+    0x68, 0x00,  // 202  LD V8, 0
+    0x69, 0x00,  // 204  LD V9, 0
+    0x6F, 0x05,  // 206  LD VF, 0x05
+    0x6A, 0x00,  // 208  LD VA, 0
+    0x6B, 0x00,  // 20A  LD VB, 0
+    0x8A, 0xB1,  // 20C  OR VA, VB
+    0xFF, 0x29,  // 20E  LD F, VF   ; 0 when logic ops reset VF
+    0xD8, 0x95,  // 210  DRW V8, V9, 5
+    0x78, 0x06,  // 212  ADD V8, 6
+    0x6A, 0x02,  // 214  LD VA, 0x02
+    0x6B, 0x08,  // 216  LD VB, 0x08
+    0x8A, 0xB6,  // 218  SHR VA, VB
+    0xFA, 0x29,  // 21A  LD F, VA   ; 4 when shifts use VY
+    0xD8, 0x95,  // 21C  DRW V8, V9, 5
+    0x78, 0x06,  // 21E  ADD V8, 6
+    0xA2, 0x63,  // 220  LD I, scratch
+    0x60, 0x01,  // 222  LD V0, 1
+    0xF0, 0x55,  // 224  LD [I], V0
+    0x60, 0x02,  // 226  LD V0, 2
+    0xF0, 0x55,  // 228  LD [I], V0
+    0xA2, 0x63,  // 22A  LD I, scratch
+    0xF0, 0x65,  // 22C  LD V0, [I]
+    0x8A, 0x00,  // 22E  LD VA, V0
+    0xFA, 0x29,  // 230  LD F, VA   ; 1 when FX55 increments I
+    0xD8, 0x95,  // 232  DRW V8, V9, 5
+    0x78, 0x06,  // 234  ADD V8, 6
+    0x60, 0x00,  // 236  LD V0, 0
+    0x62, 0x04,  // 238  LD V2, 4
+    0xB2, 0x3C,  // 23A  JP V0, table   ; V2 with the quirk, X being the 2 of 0x2NN
+    // table:
+    0x6A, 0x00,  // 23C  LD VA, 0
+    0x12, 0x44,  // 23E  JP jumped
+    0x6A, 0x01,  // 240  LD VA, 1
+    0x12, 0x44,  // 242  JP jumped
+    // jumped:
+    0xFA, 0x29,  // 244  LD F, VA   ; 1 when BNNN uses VX
+    0xD8, 0x95,  // 246  DRW V8, V9, 5
+    0x78, 0x06,  // 248  ADD V8, 6
+    0x68, 0x3C,  // 24A  LD V8, 60
+    0x69, 0x14,  // 24C  LD V9, 20
+    0xA2, 0x62,  // 24E  LD I, bar
+    0xD8, 0x91,  // 250  DRW V8, V9, 1
+    0x68, 0x01,  // 252  LD V8, 1
+    0xD8, 0x91,  // 254  DRW V8, V9, 1   ; collides only if the first bar wrapped
+    0x68, 0x18,  // 256  LD V8, 24
+    0x69, 0x00,  // 258  LD V9, 0
+    0xFF, 0x29,  // 25A  LD F, VF   ; 0 when sprites clip
+    0xD8, 0x95,  // 25C  DRW V8, V9, 5
+    0x78, 0x06,  // 25E  ADD V8, 6
+    // done:
+    0x12, 0x60,  // 260  JP done
+    // bar:
+    0xFF,  // 262  bar sprite
+    // scratch:
+    0x00, 0x00,  // 263  scratch
+];
+
+/// Draws the key FX0A returns, then waits for A to be held with EX9E and released with EXA1.
+pub const KEYPAD: &[u8] = &[
+    0x00, 0xE0,  // 200  CLS
+    0x68, 0x00,  // 202  LD V8, 0
+    0x69, 0x00,  // 204  LD V9, 0
+    0xFA, 0x0A,  // 206  LD VA, K
+    0xFA, 0x29,  // 208  LD F, VA   ; the key FX0A returned
+    0xD8, 0x95,  // 20A  DRW V8, V9, 5
+    0x78, 0x06,  // 20C  ADD V8, 6
+    // wait_down:
+    0x6B, 0x0A,  // 20E  LD VB, 0x0A
+    0xEB, 0x9E,  // 210  SKP VB
+    0x12, 0x0E,  // 212  JP wait_down
+    0xFB, 0x29,  // 214  LD F, VB   ; A once it is held
+    0xD8, 0x95,  // 216  DRW V8, V9, 5
+    0x78, 0x06,  // 218  ADD V8, 6
+    // wait_up:
+    0xEB, 0xA1,  // 21A  SKNP VB
+    0x12, 0x1A,  // 21C  JP wait_up
+    0x6C, 0x00,  // 21E  LD VC, 0
+    0xFC, 0x29,  // 220  LD F, VC   ; 0 once it is released
+    0xD8, 0x95,  // 222  DRW V8, V9, 5
+    0x78, 0x06,  // 224  ADD V8, 6
+    // done:
+    0x12, 0x26,  // 226  JP done
+];
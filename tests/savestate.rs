@@ -0,0 +1,131 @@
+//! Save states must load back into the same machine, and states written by older releases
+//! must keep loading. `fixtures/state-v1.bin` was saved from `known_cpu` by version 1.
+
+extern crate chip8;
+
+use std::fs;
+use std::path::PathBuf;
+
+use chip8::cpu::{CPU, KeyWait};
+use chip8::memory::{self, RAM};
+use chip8::quirks::Quirks;
+
+/// Sets up timers, a subroutine call and a sprite, then waits in `FX0A` for key 7 to be released.
+const PROGRAM: &[u8] = &[
+    0x60, 0x08, // V0 = 8
+    0x61, 0x04, // V1 = 4
+    0x6A, 0x12, // VA = 0x12
+    0x62, 0x1E, // V2 = 30
+    0xF2, 0x15, // delay timer = V2
+    0xF2, 0x18, // sound timer = V2
+    0x22, 0x10, // call 0x210
+    0x12, 0x0E, // loop forever
+    0xF0, 0x29, // I = sprite for the digit in V0
+    0xD0, 0x15, // draw it at (V0, V1)
+    0xF5, 0x0A, // wait for a key, into V5
+    0x00, 0xEE, // return
+];
+
+fn keys(pressed: &[usize]) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for &key in pressed {
+        keys[key] = true;
+    }
+    keys
+}
+
+fn fresh_cpu() -> CPU {
+    let mut ram = RAM::new();
+    ram.memory[memory::PROGRAM_START..memory::PROGRAM_START + PROGRAM.len()].copy_from_slice(PROGRAM);
+    CPU::new(ram.memory, Quirks::cosmac_vip(), 0)
+}
+
+fn known_cpu() -> CPU {
+    let mut cpu = fresh_cpu();
+    cpu.cpu_cycle(keys(&[])).unwrap();
+    cpu.cpu_cycle(keys(&[])).unwrap();
+    cpu.cpu_cycle(keys(&[7])).unwrap();
+    cpu
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name);
+    fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+#[test]
+fn save_then_load_restores_the_machine() {
+    let saved = known_cpu();
+    let state = saved.save_state();
+
+    let mut loaded = fresh_cpu();
+    loaded.load_state(&state).unwrap();
+
+    assert_eq!(loaded.registers(), saved.registers());
+    assert_eq!(loaded.register_i(), saved.register_i());
+    assert_eq!(loaded.program_counter(), saved.program_counter());
+    assert_eq!(loaded.stack(), saved.stack());
+    assert_eq!(loaded.delay_timer(), saved.delay_timer());
+    assert_eq!(loaded.sound_timer(), saved.sound_timer());
+    assert_eq!(loaded.memory(), saved.memory());
+    assert_eq!(loaded.video_memory().pixels(), saved.video_memory().pixels());
+    assert_eq!(loaded.key_wait(), saved.key_wait());
+    assert_eq!(loaded.save_state(), state);
+}
+
+#[test]
+fn loads_a_version_1_state() {
+    let state = fixture("state-v1.bin");
+    assert_eq!(&state[..6], b"C8SS\x01\x00");
+
+    let mut cpu = fresh_cpu();
+    cpu.load_state(&state).unwrap();
+
+    assert_eq!(cpu.registers(), &[8, 4, 30, 0, 0, 0, 0, 0, 0, 0, 0x12, 0, 0, 0, 0, 0]);
+    assert_eq!(cpu.register_i(), 8 * 5);
+    assert_eq!(cpu.program_counter(), 0x216);
+    assert_eq!(cpu.stack(), &[0x20e]);
+    assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (28, 28));
+    assert_eq!(&cpu.memory()[memory::PROGRAM_START..memory::PROGRAM_START + PROGRAM.len()], PROGRAM);
+    assert_eq!(cpu.key_wait(), KeyWait::Release { x: 5, key: 7 });
+
+    let lit: Vec<(usize, usize)> = (0..5)
+        .flat_map(|y| (0..8).map(move |x| (x, y)))
+        .filter(|&(x, y)| cpu.video_memory().get(8 + x, 4 + y) != 0)
+        .collect();
+    assert_eq!(lit.len(), 16, "the digit 8 is drawn at (8, 4)");
+
+    // Releasing the key finishes FX0A and the subroutine returns.
+    cpu.cpu_cycle(keys(&[])).unwrap();
+    assert_eq!(cpu.registers()[5], 7);
+    assert_eq!(cpu.program_counter(), 0x20e);
+}
+
+#[test]
+fn rejects_other_files() {
+    let mut cpu = fresh_cpu();
+    assert!(cpu.load_state(b"C8MV\x01\x00").is_err());
+    assert!(cpu.load_state(&fixture("state-v1.bin")[..100]).is_err());
+
+    let mut future = fixture("state-v1.bin");
+    future[4] = 0xff;
+    assert!(cpu.load_state(&future).is_err());
+}
+
+#[test]
+fn rejects_framebuffer_sizes_no_display_has() {
+    let state = known_cpu().save_state();
+    // The width follows the header, registers, I, PC, stack, stack pointer, timers and memory.
+    let size_at = 6 + 16 + 4 + 4 + 16 * 4 + 3 + 4 + memory::MEMORY_SIZE;
+    assert_eq!(&state[size_at..size_at + 4], &[64, 0, 32, 0]);
+
+    // Same number of pixels, but not a CHIP-8 resolution.
+    let mut transposed = state.clone();
+    transposed[size_at..size_at + 4].copy_from_slice(&[32, 0, 64, 0]);
+    assert!(fresh_cpu().load_state(&transposed).is_err());
+
+    let mut empty = state[..size_at].to_vec();
+    empty.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+    empty.extend_from_slice(&state[size_at + 8 + 64 * 32..]);
+    assert!(fresh_cpu().load_state(&empty).is_err());
+}